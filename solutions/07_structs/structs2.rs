@@ -1,10 +1,22 @@
-#[derive(Debug)]
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum OrderChannel {
+    Phone,
+    Mobile,
+    Email,
+}
+
+#[derive(Clone, Debug)]
 struct Order {
     name: String,
     year: u32,
-    made_by_phone: bool,
-    made_by_mobile: bool,
-    made_by_email: bool,
+    channel: OrderChannel,
     item_number: u32,
     count: u32,
 }
@@ -13,14 +25,471 @@ fn create_order_template() -> Order {
     Order {
         name: String::from("Bob"),
         year: 2019,
-        made_by_phone: false,
-        made_by_mobile: false,
-        made_by_email: true,
+        channel: OrderChannel::Email,
         item_number: 123,
         count: 0,
     }
 }
 
+#[derive(PartialEq, Debug)]
+enum OrderError {
+    EmptyName,
+    ImplausibleYear(u32),
+    ZeroCount,
+}
+
+impl fmt::Display for OrderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OrderError::EmptyName => f.write_str("order name is empty"),
+            OrderError::ImplausibleYear(year) => write!(f, "{year} isn't a plausible order year"),
+            OrderError::ZeroCount => f.write_str("order count is zero"),
+        }
+    }
+}
+
+impl Error for OrderError {}
+
+// Starts from an existing order (the template by default) and overrides some
+// of its fields, like struct update syntax does, but validates the result.
+#[derive(Debug)]
+struct OrderBuilder {
+    order: Order,
+}
+
+impl OrderBuilder {
+    const MIN_YEAR: u32 = 1990;
+    const MAX_YEAR: u32 = 2100;
+
+    fn new() -> Self {
+        Self::from(create_order_template())
+    }
+
+    fn name(mut self, name: impl Into<String>) -> Self {
+        self.order.name = name.into();
+        self
+    }
+
+    fn year(mut self, year: u32) -> Self {
+        self.order.year = year;
+        self
+    }
+
+    fn channel(mut self, channel: OrderChannel) -> Self {
+        self.order.channel = channel;
+        self
+    }
+
+    fn item_number(mut self, item_number: u32) -> Self {
+        self.order.item_number = item_number;
+        self
+    }
+
+    fn count(mut self, count: u32) -> Self {
+        self.order.count = count;
+        self
+    }
+
+    fn build(self) -> Result<Order, OrderError> {
+        let order = self.order;
+        if order.name.trim().is_empty() {
+            return Err(OrderError::EmptyName);
+        }
+        if !(Self::MIN_YEAR..=Self::MAX_YEAR).contains(&order.year) {
+            return Err(OrderError::ImplausibleYear(order.year));
+        }
+        if order.count == 0 {
+            return Err(OrderError::ZeroCount);
+        }
+
+        Ok(order)
+    }
+}
+
+impl From<Order> for OrderBuilder {
+    fn from(order: Order) -> Self {
+        Self { order }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+enum TemplateErrorKind {
+    MissingSectionName,
+    FieldOutsideSection,
+    DuplicateSection(String),
+    MissingEquals,
+    UnknownField(String),
+    InvalidNumber(String),
+    InvalidChannel(String),
+}
+
+#[derive(Debug)]
+enum TemplateError {
+    Io(io::Error),
+    // `line` starts at 1, like in text editors.
+    Parse {
+        line: usize,
+        kind: TemplateErrorKind,
+    },
+}
+
+impl From<io::Error> for TemplateError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (line, kind) = match self {
+            TemplateError::Io(err) => return write!(f, "failed to read templates: {err}"),
+            TemplateError::Parse { line, kind } => (line, kind),
+        };
+
+        write!(f, "line {line}: ")?;
+        match kind {
+            TemplateErrorKind::MissingSectionName => f.write_str("section name is empty"),
+            TemplateErrorKind::FieldOutsideSection => f.write_str("field outside of a section"),
+            TemplateErrorKind::DuplicateSection(name) => write!(f, "duplicate section [{name}]"),
+            TemplateErrorKind::MissingEquals => f.write_str("expected `key = value`"),
+            TemplateErrorKind::UnknownField(key) => write!(f, "unknown field {key:?}"),
+            TemplateErrorKind::InvalidNumber(value) => write!(f, "{value:?} isn't a number"),
+            TemplateErrorKind::InvalidChannel(value) => {
+                write!(f, "{value:?} isn't one of phone, mobile or email")
+            }
+        }
+    }
+}
+
+impl Error for TemplateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TemplateError::Io(err) => Some(err),
+            TemplateError::Parse { .. } => None,
+        }
+    }
+}
+
+// Named order templates, one per section:
+//
+//     # Comments and blank lines are ignored.
+//     [alice]
+//     name = Alice
+//     channel = phone
+//
+// Fields that a section doesn't set are taken from `create_order_template`.
+#[derive(Debug)]
+struct OrderTemplates {
+    templates: HashMap<String, Order>,
+}
+
+impl OrderTemplates {
+    fn load(path: impl AsRef<Path>) -> Result<Self, TemplateError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    fn parse(text: &str) -> Result<Self, TemplateError> {
+        let mut templates = HashMap::new();
+        let mut current: Option<(String, Order)> = None;
+
+        for (index, line) in text.lines().enumerate() {
+            let error = |kind| TemplateError::Parse {
+                line: index + 1,
+                kind,
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                let name = name.trim();
+                if name.is_empty() {
+                    return Err(error(TemplateErrorKind::MissingSectionName));
+                }
+                if let Some((name, order)) = current.take() {
+                    templates.insert(name, order);
+                }
+                if templates.contains_key(name) {
+                    return Err(error(TemplateErrorKind::DuplicateSection(name.to_string())));
+                }
+                current = Some((name.to_string(), create_order_template()));
+                continue;
+            }
+
+            let Some((_, order)) = current.as_mut() else {
+                return Err(error(TemplateErrorKind::FieldOutsideSection));
+            };
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(error(TemplateErrorKind::MissingEquals));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let number = || {
+                value
+                    .parse()
+                    .map_err(|_| error(TemplateErrorKind::InvalidNumber(value.to_string())))
+            };
+
+            match key {
+                "name" => order.name = value.to_string(),
+                "year" => order.year = number()?,
+                "item_number" => order.item_number = number()?,
+                "count" => order.count = number()?,
+                "channel" => {
+                    order.channel = match value.to_lowercase().as_str() {
+                        "phone" => OrderChannel::Phone,
+                        "mobile" => OrderChannel::Mobile,
+                        "email" => OrderChannel::Email,
+                        _ => {
+                            return Err(error(TemplateErrorKind::InvalidChannel(value.to_string())))
+                        }
+                    }
+                }
+                _ => return Err(error(TemplateErrorKind::UnknownField(key.to_string()))),
+            }
+        }
+
+        if let Some((name, order)) = current {
+            templates.insert(name, order);
+        }
+
+        Ok(Self { templates })
+    }
+
+    fn get(&self, name: &str) -> Option<&Order> {
+        self.templates.get(name)
+    }
+
+    fn builder(&self, name: &str) -> Option<OrderBuilder> {
+        self.get(name).cloned().map(OrderBuilder::from)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum TaxClass {
+    Standard,
+    Reduced,
+    Exempt,
+}
+
+impl TaxClass {
+    fn percent(self) -> u64 {
+        match self {
+            TaxClass::Standard => 20,
+            TaxClass::Reduced => 10,
+            TaxClass::Exempt => 0,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            TaxClass::Standard => "standard",
+            TaxClass::Reduced => "reduced",
+            TaxClass::Exempt => "exempt",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CatalogItem {
+    name: String,
+    unit_price_in_cents: u32,
+    tax_class: TaxClass,
+}
+
+#[derive(Debug)]
+struct Catalog {
+    items: HashMap<u32, CatalogItem>,
+}
+
+impl Catalog {
+    fn new() -> Self {
+        Self {
+            items: HashMap::new(),
+        }
+    }
+
+    fn with_item(
+        mut self,
+        item_number: u32,
+        name: &str,
+        unit_price_in_cents: u32,
+        tax_class: TaxClass,
+    ) -> Self {
+        self.items.insert(
+            item_number,
+            CatalogItem {
+                name: name.to_string(),
+                unit_price_in_cents,
+                tax_class,
+            },
+        );
+        self
+    }
+}
+
+#[derive(PartialEq, Debug)]
+enum InvoiceError {
+    UnknownItem(u32),
+    Overflow,
+}
+
+impl fmt::Display for InvoiceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvoiceError::UnknownItem(item_number) => {
+                write!(f, "item #{item_number} isn't in the catalog")
+            }
+            InvoiceError::Overflow => f.write_str("invoice amount is too large"),
+        }
+    }
+}
+
+impl Error for InvoiceError {}
+
+#[derive(PartialEq, Debug)]
+struct InvoiceLine {
+    item_number: u32,
+    description: String,
+    count: u32,
+    unit_price_in_cents: u32,
+    amount_in_cents: u64,
+    tax_class: TaxClass,
+}
+
+#[derive(PartialEq, Debug)]
+struct TaxTotal {
+    tax_class: TaxClass,
+    net_in_cents: u64,
+    tax_in_cents: u64,
+}
+
+#[derive(PartialEq, Debug)]
+struct Invoice {
+    lines: Vec<InvoiceLine>,
+    subtotal_in_cents: u64,
+    // Sorted by tax class.
+    taxes: Vec<TaxTotal>,
+    total_in_cents: u64,
+}
+
+impl Invoice {
+    fn new(orders: &[Order], catalog: &Catalog) -> Result<Self, InvoiceError> {
+        let mut lines = Vec::new();
+        let mut subtotal_in_cents: u64 = 0;
+        let mut nets: Vec<(TaxClass, u64)> = Vec::new();
+
+        for order in orders {
+            let item = catalog
+                .items
+                .get(&order.item_number)
+                .ok_or(InvoiceError::UnknownItem(order.item_number))?;
+            // Two `u32` factors always fit in a `u64`.
+            let amount_in_cents = u64::from(order.count) * u64::from(item.unit_price_in_cents);
+            subtotal_in_cents = subtotal_in_cents
+                .checked_add(amount_in_cents)
+                .ok_or(InvoiceError::Overflow)?;
+
+            match nets.iter_mut().find(|(class, _)| *class == item.tax_class) {
+                Some((_, net)) => *net += amount_in_cents,
+                None => nets.push((item.tax_class, amount_in_cents)),
+            }
+
+            lines.push(InvoiceLine {
+                item_number: order.item_number,
+                description: item.name.clone(),
+                count: order.count,
+                unit_price_in_cents: item.unit_price_in_cents,
+                amount_in_cents,
+                tax_class: item.tax_class,
+            });
+        }
+
+        nets.sort();
+        let mut total_in_cents = subtotal_in_cents;
+        let mut taxes = Vec::new();
+        for (tax_class, net_in_cents) in nets {
+            // Rounded half up to the nearest cent.
+            let tax_in_cents = net_in_cents
+                .checked_mul(tax_class.percent())
+                .and_then(|tax| tax.checked_add(50))
+                .ok_or(InvoiceError::Overflow)?
+                / 100;
+            total_in_cents = total_in_cents
+                .checked_add(tax_in_cents)
+                .ok_or(InvoiceError::Overflow)?;
+            taxes.push(TaxTotal {
+                tax_class,
+                net_in_cents,
+                tax_in_cents,
+            });
+        }
+
+        Ok(Self {
+            lines,
+            subtotal_in_cents,
+            taxes,
+            total_in_cents,
+        })
+    }
+}
+
+fn format_cents(cents: u64) -> String {
+    format!("{}.{:02}", cents / 100, cents % 100)
+}
+
+// Renders a fixed-width document, 56 columns wide. Descriptions that don't fit
+// are cut off.
+impl fmt::Display for Invoice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const RULE: &str = "--------------------------------------------------------";
+
+        writeln!(
+            f,
+            "{:<24}{:>8}{:>12}{:>12}",
+            "Item", "Qty", "Unit price", "Amount",
+        )?;
+        writeln!(f, "{RULE}")?;
+        for line in &self.lines {
+            let description: String = format!("#{} {}", line.item_number, line.description)
+                .chars()
+                .take(24)
+                .collect();
+            writeln!(
+                f,
+                "{:<24}{:>8}{:>12}{:>12}",
+                description,
+                line.count,
+                format_cents(line.unit_price_in_cents.into()),
+                format_cents(line.amount_in_cents),
+            )?;
+        }
+        writeln!(f, "{RULE}")?;
+        writeln!(
+            f,
+            "{:<44}{:>12}",
+            "Subtotal",
+            format_cents(self.subtotal_in_cents)
+        )?;
+        for tax in &self.taxes {
+            let label = format!(
+                "Tax {} {}% on {}",
+                tax.tax_class.name(),
+                tax.tax_class.percent(),
+                format_cents(tax.net_in_cents),
+            );
+            writeln!(f, "{:<44}{:>12}", label, format_cents(tax.tax_in_cents))?;
+        }
+        writeln!(f, "{RULE}")?;
+        writeln!(
+            f,
+            "{:<44}{:>12}",
+            "Total",
+            format_cents(self.total_in_cents)
+        )
+    }
+}
+
 fn main() {
     // You can optionally experiment here.
 }
@@ -42,10 +511,265 @@ mod tests {
 
         assert_eq!(your_order.name, "Hacker in Rust");
         assert_eq!(your_order.year, order_template.year);
-        assert_eq!(your_order.made_by_phone, order_template.made_by_phone);
-        assert_eq!(your_order.made_by_mobile, order_template.made_by_mobile);
-        assert_eq!(your_order.made_by_email, order_template.made_by_email);
+        assert_eq!(your_order.channel, order_template.channel);
+        assert_eq!(your_order.item_number, order_template.item_number);
+        assert_eq!(your_order.count, 1);
+    }
+
+    #[test]
+    fn your_order_from_builder() {
+        let order_template = create_order_template();
+
+        let your_order = OrderBuilder::new()
+            .name("Hacker in Rust")
+            .count(1)
+            .build()
+            .unwrap();
+
+        assert_eq!(your_order.name, "Hacker in Rust");
+        assert_eq!(your_order.year, order_template.year);
+        assert_eq!(your_order.channel, order_template.channel);
         assert_eq!(your_order.item_number, order_template.item_number);
         assert_eq!(your_order.count, 1);
     }
+
+    #[test]
+    fn override_every_field() {
+        let order = OrderBuilder::new()
+            .name("Alice")
+            .year(2024)
+            .channel(OrderChannel::Phone)
+            .item_number(42)
+            .count(3)
+            .build()
+            .unwrap();
+
+        assert_eq!(order.name, "Alice");
+        assert_eq!(order.year, 2024);
+        assert_eq!(order.channel, OrderChannel::Phone);
+        assert_eq!(order.item_number, 42);
+        assert_eq!(order.count, 3);
+    }
+
+    #[test]
+    fn build_from_existing_order() {
+        let previous_order = OrderBuilder::new()
+            .channel(OrderChannel::Mobile)
+            .count(2)
+            .build()
+            .unwrap();
+
+        let order = OrderBuilder::from(previous_order).count(5).build().unwrap();
+
+        assert_eq!(order.channel, OrderChannel::Mobile);
+        assert_eq!(order.count, 5);
+    }
+
+    #[test]
+    fn fail_building_invalid_orders() {
+        assert_eq!(
+            OrderBuilder::new().name(" ").count(1).build().unwrap_err(),
+            OrderError::EmptyName,
+        );
+        assert_eq!(
+            OrderBuilder::new().year(1789).count(1).build().unwrap_err(),
+            OrderError::ImplausibleYear(1789),
+        );
+        assert_eq!(
+            OrderBuilder::new().build().unwrap_err(),
+            OrderError::ZeroCount,
+        );
+    }
+
+    const TEMPLATES: &str = "\
+# Regular customers
+[alice]
+name = Alice
+year = 2024
+channel = Phone
+item_number = 42
+
+[carol]
+name = Carol
+count = 7
+";
+
+    #[test]
+    fn parse_templates() {
+        let templates = OrderTemplates::parse(TEMPLATES).unwrap();
+
+        let alice = templates.get("alice").unwrap();
+        assert_eq!(alice.name, "Alice");
+        assert_eq!(alice.year, 2024);
+        assert_eq!(alice.channel, OrderChannel::Phone);
+        assert_eq!(alice.item_number, 42);
+        assert_eq!(alice.count, 0);
+
+        // Missing fields fall back to the built-in template.
+        let carol = templates.get("carol").unwrap();
+        let order_template = create_order_template();
+        assert_eq!(carol.name, "Carol");
+        assert_eq!(carol.year, order_template.year);
+        assert_eq!(carol.channel, order_template.channel);
+        assert_eq!(carol.item_number, order_template.item_number);
+        assert_eq!(carol.count, 7);
+
+        assert!(templates.get("bob").is_none());
+    }
+
+    #[test]
+    fn build_order_from_named_template() {
+        let templates = OrderTemplates::parse(TEMPLATES).unwrap();
+
+        let order = templates
+            .builder("alice")
+            .unwrap()
+            .count(2)
+            .build()
+            .unwrap();
+
+        assert_eq!(order.name, "Alice");
+        assert_eq!(order.count, 2);
+    }
+
+    #[test]
+    fn load_templates_from_file() {
+        let path = std::env::temp_dir().join(format!("order_templates_{}.txt", std::process::id()));
+        fs::write(&path, TEMPLATES).unwrap();
+
+        let templates = OrderTemplates::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(templates.unwrap().get("alice").unwrap().name, "Alice");
+        assert!(matches!(
+            OrderTemplates::load(&path),
+            Err(TemplateError::Io(_)),
+        ));
+    }
+
+    fn parse_error(text: &str) -> (usize, TemplateErrorKind) {
+        match OrderTemplates::parse(text) {
+            Err(TemplateError::Parse { line, kind }) => (line, kind),
+            other => panic!("expected a parse error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn fail_parsing_with_line_numbers() {
+        assert_eq!(
+            parse_error("name = Bob"),
+            (1, TemplateErrorKind::FieldOutsideSection),
+        );
+        assert_eq!(
+            parse_error("[a]\n\n[ ]"),
+            (3, TemplateErrorKind::MissingSectionName),
+        );
+        assert_eq!(
+            parse_error("[a]\n[b]\n[a]"),
+            (3, TemplateErrorKind::DuplicateSection(String::from("a"))),
+        );
+        assert_eq!(
+            parse_error("[a]\n# comment\nname Bob"),
+            (3, TemplateErrorKind::MissingEquals),
+        );
+        assert_eq!(
+            parse_error("[a]\ncolor = red"),
+            (2, TemplateErrorKind::UnknownField(String::from("color"))),
+        );
+        assert_eq!(
+            parse_error("[a]\nyear = soon"),
+            (2, TemplateErrorKind::InvalidNumber(String::from("soon"))),
+        );
+        assert_eq!(
+            parse_error("[a]\nchannel = fax"),
+            (2, TemplateErrorKind::InvalidChannel(String::from("fax"))),
+        );
+        assert_eq!(
+            OrderTemplates::parse("[a]\nyear = soon")
+                .unwrap_err()
+                .to_string(),
+            "line 2: \"soon\" isn't a number",
+        );
+    }
+
+    fn catalog() -> Catalog {
+        Catalog::new()
+            .with_item(123, "Rust book", 3999, TaxClass::Reduced)
+            .with_item(42, "Ferris plush toy", 1250, TaxClass::Standard)
+            .with_item(7, "Gift voucher", 2000, TaxClass::Exempt)
+    }
+
+    fn order(item_number: u32, count: u32) -> Order {
+        OrderBuilder::new()
+            .item_number(item_number)
+            .count(count)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn invoice_totals() {
+        let orders = [order(123, 2), order(42, 3), order(123, 1), order(7, 1)];
+
+        let invoice = Invoice::new(&orders, &catalog()).unwrap();
+
+        assert_eq!(invoice.lines.len(), 4);
+        assert_eq!(invoice.lines[1].description, "Ferris plush toy");
+        assert_eq!(invoice.lines[1].amount_in_cents, 3750);
+        assert_eq!(invoice.subtotal_in_cents, 17747);
+        assert_eq!(
+            invoice.taxes,
+            [
+                TaxTotal {
+                    tax_class: TaxClass::Standard,
+                    net_in_cents: 3750,
+                    tax_in_cents: 750,
+                },
+                TaxTotal {
+                    tax_class: TaxClass::Reduced,
+                    net_in_cents: 11997,
+                    tax_in_cents: 1200,
+                },
+                TaxTotal {
+                    tax_class: TaxClass::Exempt,
+                    net_in_cents: 2000,
+                    tax_in_cents: 0,
+                },
+            ],
+        );
+        assert_eq!(invoice.total_in_cents, 19697);
+    }
+
+    #[test]
+    fn render_invoice() {
+        let orders = [order(123, 2), order(42, 3)];
+
+        let invoice = Invoice::new(&orders, &catalog()).unwrap();
+
+        assert_eq!(
+            invoice.to_string(),
+            "\
+Item                         Qty  Unit price      Amount
+--------------------------------------------------------
+#123 Rust book                 2       39.99       79.98
+#42 Ferris plush toy           3       12.50       37.50
+--------------------------------------------------------
+Subtotal                                          117.48
+Tax standard 20% on 37.50                           7.50
+Tax reduced 10% on 79.98                            8.00
+--------------------------------------------------------
+Total                                             132.98
+",
+        );
+    }
+
+    #[test]
+    fn fail_invoicing_unknown_item() {
+        let orders = [order(123, 1), order(999, 1)];
+
+        assert_eq!(
+            Invoice::new(&orders, &catalog()),
+            Err(InvoiceError::UnknownItem(999)),
+        );
+    }
 }
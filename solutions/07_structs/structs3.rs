@@ -1,23 +1,544 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+#[derive(PartialEq, Debug)]
+enum PackageError {
+    TooLight(u32),
+    TooHeavy(u32),
+    EmptySenderCountry,
+    EmptyRecipientCountry,
+    InvalidCountry(String),
+    InvalidDimensions(Dimensions),
+    ZeroVolumetricDivisor,
+    DomesticCustomsDeclaration,
+    EmptyItemDescription,
+}
+
+impl fmt::Display for PackageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PackageError::TooLight(_) => write!(
+                f,
+                "Can't ship a package with weight below {} grams",
+                Package::MIN_WEIGHT_IN_GRAMS,
+            ),
+            PackageError::TooHeavy(_) => write!(
+                f,
+                "Can't ship a package with weight above {} grams",
+                Package::MAX_WEIGHT_IN_GRAMS,
+            ),
+            PackageError::EmptySenderCountry => f.write_str("sender country is empty"),
+            PackageError::EmptyRecipientCountry => f.write_str("recipient country is empty"),
+            PackageError::InvalidCountry(country) => {
+                write!(f, "invalid country name: {country:?}")
+            }
+            PackageError::InvalidDimensions(dimensions) => write!(
+                f,
+                "invalid package dimensions: {}x{}x{} cm",
+                dimensions.length_in_cm, dimensions.width_in_cm, dimensions.height_in_cm,
+            ),
+            PackageError::ZeroVolumetricDivisor => f.write_str("volumetric divisor is zero"),
+            PackageError::DomesticCustomsDeclaration => {
+                f.write_str("domestic packages don't need a customs declaration")
+            }
+            PackageError::EmptyItemDescription => f.write_str("declared items need a description"),
+        }
+    }
+}
+
+impl Error for PackageError {}
+
+// A country name is valid if it isn't padded with whitespace and only contains
+// letters, spaces, hyphens, dots or apostrophes ("Bosnia-Herzegovina",
+// "Côte d'Ivoire", "St. Lucia").
+fn validate_country(country: &str) -> Result<(), PackageError> {
+    let valid = country.trim() == country
+        && country
+            .chars()
+            .all(|c| c.is_alphabetic() || matches!(c, ' ' | '-' | '.' | '\''));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(PackageError::InvalidCountry(country.to_string()))
+    }
+}
+
+#[derive(PartialEq, Debug)]
+enum FeeError {
+    NoTariff {
+        sender_country: String,
+        recipient_country: String,
+    },
+    Overflow,
+    MissingCustomsDeclaration,
+    NoDutyRate(String),
+}
+
+impl fmt::Display for FeeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FeeError::NoTariff {
+                sender_country,
+                recipient_country,
+            } => write!(
+                f,
+                "no tariff for packages from {sender_country} to {recipient_country}",
+            ),
+            FeeError::Overflow => f.write_str("fees don't fit in a `u32` amount of cents"),
+            FeeError::MissingCustomsDeclaration => {
+                f.write_str("international packages need a customs declaration")
+            }
+            FeeError::NoDutyRate(country) => write!(f, "no duty rate for packages to {country}"),
+        }
+    }
+}
+
+impl Error for FeeError {}
+
+// A weight bracket applies its rate to packages weighing at least
+// `from_grams`. The heaviest matching bracket wins.
+#[derive(Debug)]
+struct WeightBracket {
+    from_grams: u32,
+    cents_per_gram: u32,
+}
+
+#[derive(Debug)]
+struct Tariff {
+    brackets: Vec<WeightBracket>,
+    minimum_charge: u32,
+}
+
+impl Tariff {
+    fn new(cents_per_gram: u32) -> Self {
+        Self {
+            brackets: vec![WeightBracket {
+                from_grams: 0,
+                cents_per_gram,
+            }],
+            minimum_charge: 0,
+        }
+    }
+
+    fn with_bracket(mut self, from_grams: u32, cents_per_gram: u32) -> Self {
+        self.brackets
+            .retain(|bracket| bracket.from_grams != from_grams);
+        self.brackets.push(WeightBracket {
+            from_grams,
+            cents_per_gram,
+        });
+        self.brackets.sort_by_key(|bracket| bracket.from_grams);
+        self
+    }
+
+    fn with_minimum_charge(mut self, minimum_charge: u32) -> Self {
+        self.minimum_charge = minimum_charge;
+        self
+    }
+
+    fn price(&self, weight_in_grams: u32) -> Result<u32, FeeError> {
+        // `new` always adds a bracket starting at 0 grams.
+        let bracket = self
+            .brackets
+            .iter()
+            .rev()
+            .find(|bracket| bracket.from_grams <= weight_in_grams)
+            .unwrap();
+
+        let fees = weight_in_grams
+            .checked_mul(bracket.cents_per_gram)
+            .ok_or(FeeError::Overflow)?;
+
+        Ok(fees.max(self.minimum_charge))
+    }
+}
+
+// Tariffs for international packages are looked up by route first, then by the
+// zone of the recipient country. Domestic packages always use `domestic`.
+#[derive(Debug)]
+struct TariffTable {
+    domestic: Tariff,
+    routes: HashMap<(String, String), Tariff>,
+    zones: HashMap<String, String>,
+    zone_tariffs: HashMap<String, Tariff>,
+}
+
+impl TariffTable {
+    fn new(domestic: Tariff) -> Self {
+        Self {
+            domestic,
+            routes: HashMap::new(),
+            zones: HashMap::new(),
+            zone_tariffs: HashMap::new(),
+        }
+    }
+
+    fn with_route(mut self, sender_country: &str, recipient_country: &str, tariff: Tariff) -> Self {
+        self.routes.insert(
+            (sender_country.to_string(), recipient_country.to_string()),
+            tariff,
+        );
+        self
+    }
+
+    fn with_zone(mut self, country: &str, zone: &str) -> Self {
+        self.zones.insert(country.to_string(), zone.to_string());
+        self
+    }
+
+    fn with_zone_tariff(mut self, zone: &str, tariff: Tariff) -> Self {
+        self.zone_tariffs.insert(zone.to_string(), tariff);
+        self
+    }
+
+    fn tariff_for(&self, sender_country: &str, recipient_country: &str) -> Option<&Tariff> {
+        if sender_country == recipient_country {
+            return Some(&self.domestic);
+        }
+
+        let route = (sender_country.to_string(), recipient_country.to_string());
+        self.routes.get(&route).or_else(|| {
+            let zone = self.zones.get(recipient_country)?;
+            self.zone_tariffs.get(zone)
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Dimensions {
+    length_in_cm: u32,
+    width_in_cm: u32,
+    height_in_cm: u32,
+}
+
+impl Dimensions {
+    fn volume_in_cubic_cm(&self) -> Option<u64> {
+        u64::from(self.length_in_cm)
+            .checked_mul(u64::from(self.width_in_cm))?
+            .checked_mul(u64::from(self.height_in_cm))
+    }
+}
+
+#[derive(Debug)]
+struct DeclaredItem {
+    description: String,
+    quantity: u32,
+    value_in_cents: u32,
+    origin_country: String,
+}
+
+#[derive(Debug)]
+struct CustomsDeclaration {
+    items: Vec<DeclaredItem>,
+}
+
+impl CustomsDeclaration {
+    // With `exempt_returned_goods`, items made in the recipient country are
+    // left out.
+    fn dutiable_value(
+        &self,
+        recipient_country: &str,
+        exempt_returned_goods: bool,
+    ) -> Result<u32, FeeError> {
+        let mut total: u32 = 0;
+        for item in &self.items {
+            if exempt_returned_goods && item.origin_country == recipient_country {
+                continue;
+            }
+            let value = item
+                .quantity
+                .checked_mul(item.value_in_cents)
+                .ok_or(FeeError::Overflow)?;
+            total = total.checked_add(value).ok_or(FeeError::Overflow)?;
+        }
+
+        Ok(total)
+    }
+}
+
+// Shipments with a dutiable value above `threshold_in_cents` pay `percent` of
+// their whole dutiable value. Cheaper ones are duty-free.
+#[derive(Debug)]
+struct DutyRate {
+    threshold_in_cents: u32,
+    percent: u32,
+}
+
+#[derive(Debug)]
+struct DutyTable {
+    rates: HashMap<String, DutyRate>,
+    // Off by default: every declared item is dutiable.
+    exempt_returned_goods: bool,
+}
+
+impl DutyTable {
+    fn new() -> Self {
+        Self {
+            rates: HashMap::new(),
+            exempt_returned_goods: false,
+        }
+    }
+
+    // Goods coming back to the country they were made in aren't dutiable.
+    fn with_returned_goods_exempt(mut self) -> Self {
+        self.exempt_returned_goods = true;
+        self
+    }
+
+    fn with_rate(mut self, recipient_country: &str, threshold_in_cents: u32, percent: u32) -> Self {
+        self.rates.insert(
+            recipient_country.to_string(),
+            DutyRate {
+                threshold_in_cents,
+                percent,
+            },
+        );
+        self
+    }
+
+    fn duty(&self, recipient_country: &str, dutiable_value: u32) -> Result<u32, FeeError> {
+        let rate = self
+            .rates
+            .get(recipient_country)
+            .ok_or_else(|| FeeError::NoDutyRate(recipient_country.to_string()))?;
+
+        if dutiable_value <= rate.threshold_in_cents {
+            return Ok(0);
+        }
+
+        let duty = u64::from(dutiable_value) * u64::from(rate.percent) / 100;
+        u32::try_from(duty).map_err(|_| FeeError::Overflow)
+    }
+}
+
+// The transport fee and the customs duty are reported as separate lines.
+#[derive(PartialEq, Debug)]
+struct Charges {
+    transport: u32,
+    duty: u32,
+    total: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Status {
+    Created,
+    Dispatched,
+    InTransit,
+    OutForDelivery,
+    Delivered,
+    Returned,
+    Lost,
+}
+
+impl Status {
+    fn can_become(self, next: Status) -> bool {
+        use Status::*;
+
+        matches!(
+            (self, next),
+            (Created, Dispatched)
+                | (Dispatched, InTransit)
+                | (InTransit, OutForDelivery)
+                // A failed delivery attempt goes back to the depot.
+                | (OutForDelivery, InTransit)
+                | (OutForDelivery, Delivered)
+                | (InTransit | OutForDelivery, Returned)
+                | (Dispatched | InTransit | OutForDelivery, Lost)
+        )
+    }
+
+    fn is_final(self) -> bool {
+        matches!(self, Status::Delivered | Status::Returned | Status::Lost)
+    }
+}
+
+#[derive(PartialEq, Debug)]
+enum TrackingError {
+    IllegalTransition { from: Status, to: Status },
+    OutOfOrder { last: SystemTime, at: SystemTime },
+}
+
+impl fmt::Display for TrackingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TrackingError::IllegalTransition { from, to } => {
+                write!(f, "a {from:?} package can't become {to:?}")
+            }
+            TrackingError::OutOfOrder { last, at } => write!(
+                f,
+                "event at {at:?} happened before the last recorded event at {last:?}",
+            ),
+        }
+    }
+}
+
+impl Error for TrackingError {}
+
+#[derive(PartialEq, Debug)]
+struct TrackingEvent {
+    status: Status,
+    at: SystemTime,
+}
+
 #[derive(Debug)]
 struct Package {
     sender_country: String,
     recipient_country: String,
     weight_in_grams: u32,
+    dimensions: Option<Dimensions>,
+    // Cubic centimeters per kilogram of dimensional weight.
+    volumetric_divisor: u32,
+    customs_declaration: Option<CustomsDeclaration>,
+    // Every package starts out as `Created`, only transitions are recorded.
+    history: Vec<TrackingEvent>,
 }
 
 impl Package {
-    fn new(sender_country: String, recipient_country: String, weight_in_grams: u32) -> Self {
-        if weight_in_grams < 10 {
-            // This isn't how you should handle errors in Rust, but we will
-            // learn about error handling later.
-            panic!("Can't ship a package with weight below 10 grams");
+    const MIN_WEIGHT_IN_GRAMS: u32 = 10;
+    const MAX_WEIGHT_IN_GRAMS: u32 = 30_000;
+    const DEFAULT_VOLUMETRIC_DIVISOR: u32 = 5000;
+
+    fn try_new(
+        sender_country: String,
+        recipient_country: String,
+        weight_in_grams: u32,
+    ) -> Result<Self, PackageError> {
+        if weight_in_grams < Self::MIN_WEIGHT_IN_GRAMS {
+            return Err(PackageError::TooLight(weight_in_grams));
+        }
+        if weight_in_grams > Self::MAX_WEIGHT_IN_GRAMS {
+            return Err(PackageError::TooHeavy(weight_in_grams));
+        }
+        if sender_country.is_empty() {
+            return Err(PackageError::EmptySenderCountry);
         }
+        if recipient_country.is_empty() {
+            return Err(PackageError::EmptyRecipientCountry);
+        }
+        validate_country(&sender_country)?;
+        validate_country(&recipient_country)?;
 
-        Self {
+        Ok(Self {
             sender_country,
             recipient_country,
             weight_in_grams,
+            dimensions: None,
+            volumetric_divisor: Self::DEFAULT_VOLUMETRIC_DIVISOR,
+            customs_declaration: None,
+            history: Vec::new(),
+        })
+    }
+
+    fn new(sender_country: String, recipient_country: String, weight_in_grams: u32) -> Self {
+        // Prefer `try_new` when the input isn't trusted. Panicking here aborts
+        // the whole process.
+        match Self::try_new(sender_country, recipient_country, weight_in_grams) {
+            Ok(package) => package,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    fn with_dimensions(mut self, dimensions: Dimensions) -> Result<Self, PackageError> {
+        if dimensions.length_in_cm == 0
+            || dimensions.width_in_cm == 0
+            || dimensions.height_in_cm == 0
+        {
+            return Err(PackageError::InvalidDimensions(dimensions));
+        }
+
+        self.dimensions = Some(dimensions);
+        Ok(self)
+    }
+
+    fn with_volumetric_divisor(mut self, volumetric_divisor: u32) -> Result<Self, PackageError> {
+        if volumetric_divisor == 0 {
+            return Err(PackageError::ZeroVolumetricDivisor);
+        }
+
+        self.volumetric_divisor = volumetric_divisor;
+        Ok(self)
+    }
+
+    fn with_customs_declaration(
+        mut self,
+        customs_declaration: CustomsDeclaration,
+    ) -> Result<Self, PackageError> {
+        if !self.is_international() {
+            return Err(PackageError::DomesticCustomsDeclaration);
+        }
+        if customs_declaration
+            .items
+            .iter()
+            .any(|item| item.description.trim().is_empty())
+        {
+            return Err(PackageError::EmptyItemDescription);
+        }
+
+        self.customs_declaration = Some(customs_declaration);
+        Ok(self)
+    }
+
+    // The dimensional weight is rounded up to the next gram. Packages without
+    // dimensions don't have one.
+    fn volumetric_weight(&self) -> Option<u32> {
+        let volume = self.dimensions?.volume_in_cubic_cm();
+        // A volume too large for `u64` is heavier than anything we can bill.
+        let grams = volume
+            .and_then(|volume| volume.checked_mul(1000))
+            .map(|volume| volume.div_ceil(u64::from(self.volumetric_divisor)))
+            .unwrap_or(u64::MAX);
+
+        Some(u32::try_from(grams).unwrap_or(u32::MAX))
+    }
+
+    fn billable_weight(&self) -> u32 {
+        match self.volumetric_weight() {
+            Some(volumetric_weight) => self.weight_in_grams.max(volumetric_weight),
+            None => self.weight_in_grams,
+        }
+    }
+
+    fn status(&self) -> Status {
+        self.history
+            .last()
+            .map_or(Status::Created, |event| event.status)
+    }
+
+    fn history(&self) -> &[TrackingEvent] {
+        &self.history
+    }
+
+    fn transition(&mut self, status: Status, at: SystemTime) -> Result<(), TrackingError> {
+        let from = self.status();
+        if !from.can_become(status) {
+            return Err(TrackingError::IllegalTransition { from, to: status });
+        }
+        if let Some(last) = self.history.last() {
+            if at < last.at {
+                return Err(TrackingError::OutOfOrder { last: last.at, at });
+            }
         }
+
+        self.history.push(TrackingEvent { status, at });
+        Ok(())
+    }
+
+    // The time since the package was dispatched, up to `now` if it's still on
+    // its way. Packages that haven't been dispatched yet return `None`.
+    fn time_in_transit(&self, now: SystemTime) -> Option<Duration> {
+        let dispatched = self
+            .history
+            .iter()
+            .find(|event| event.status == Status::Dispatched)?;
+        let end = match self.history.last() {
+            Some(last) if last.status.is_final() => last.at,
+            _ => now,
+        };
+
+        Some(end.duration_since(dispatched.at).unwrap_or_default())
     }
 
     fn is_international(&self) -> bool {
@@ -25,9 +546,237 @@ impl Package {
         self.sender_country != self.recipient_country
     }
 
-    fn get_fees(&self, cents_per_gram: u32) -> u32 {
-        //                                  ^^^^^^ added
-        self.weight_in_grams * cents_per_gram
+    fn get_fees(&self, tariffs: &TariffTable) -> Result<u32, FeeError> {
+        let tariff = tariffs
+            .tariff_for(&self.sender_country, &self.recipient_country)
+            .ok_or_else(|| FeeError::NoTariff {
+                sender_country: self.sender_country.clone(),
+                recipient_country: self.recipient_country.clone(),
+            })?;
+
+        tariff.price(self.billable_weight())
+    }
+
+    fn get_charges(&self, tariffs: &TariffTable, duties: &DutyTable) -> Result<Charges, FeeError> {
+        let transport = self.get_fees(tariffs)?;
+
+        let duty = if self.is_international() {
+            let customs_declaration = self
+                .customs_declaration
+                .as_ref()
+                .ok_or(FeeError::MissingCustomsDeclaration)?;
+            let dutiable_value = customs_declaration
+                .dutiable_value(&self.recipient_country, duties.exempt_returned_goods)?;
+            duties.duty(&self.recipient_country, dutiable_value)?
+        } else {
+            0
+        };
+
+        Ok(Charges {
+            transport,
+            duty,
+            total: transport.checked_add(duty).ok_or(FeeError::Overflow)?,
+        })
+    }
+}
+
+#[derive(PartialEq, Debug)]
+enum ShipmentError {
+    Fee(FeeError),
+    InvalidDiscount(u32),
+    OverWeightCap {
+        weight_in_grams: u32,
+        cap_in_grams: u32,
+    },
+}
+
+impl From<FeeError> for ShipmentError {
+    fn from(err: FeeError) -> Self {
+        Self::Fee(err)
+    }
+}
+
+impl fmt::Display for ShipmentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShipmentError::Fee(err) => err.fmt(f),
+            ShipmentError::InvalidDiscount(percent_off) => {
+                write!(f, "a discount of {percent_off}% is more than 100%")
+            }
+            ShipmentError::OverWeightCap {
+                weight_in_grams,
+                cap_in_grams,
+            } => write!(
+                f,
+                "a package of {weight_in_grams} grams exceeds the shipment cap of {cap_in_grams} grams",
+            ),
+        }
+    }
+}
+
+impl Error for ShipmentError {}
+
+#[derive(PartialEq, Debug)]
+struct QuoteLine {
+    weight_in_grams: u32,
+    fees: u32,
+}
+
+// Packages with the same sender and recipient country that travel together.
+#[derive(PartialEq, Debug)]
+struct ConsignmentQuote {
+    sender_country: String,
+    recipient_country: String,
+    lines: Vec<QuoteLine>,
+    total_weight_in_grams: u32,
+    subtotal: u32,
+    discount: u32,
+    total: u32,
+}
+
+#[derive(PartialEq, Debug)]
+struct ShipmentQuote {
+    consignments: Vec<ConsignmentQuote>,
+    total: u32,
+}
+
+#[derive(Debug)]
+struct BulkDiscount {
+    min_packages: usize,
+    percent_off: u32,
+}
+
+#[derive(Debug)]
+struct Shipment {
+    packages: Vec<Package>,
+    cap_in_grams: u32,
+    discounts: Vec<BulkDiscount>,
+}
+
+impl Shipment {
+    fn new(cap_in_grams: u32) -> Self {
+        Self {
+            packages: Vec::new(),
+            cap_in_grams,
+            discounts: Vec::new(),
+        }
+    }
+
+    // Consignments with at least `min_packages` packages get `percent_off`
+    // their subtotal. The largest matching discount wins.
+    fn with_bulk_discount(
+        mut self,
+        min_packages: usize,
+        percent_off: u32,
+    ) -> Result<Self, ShipmentError> {
+        if percent_off > 100 {
+            return Err(ShipmentError::InvalidDiscount(percent_off));
+        }
+
+        self.discounts
+            .retain(|discount| discount.min_packages != min_packages);
+        self.discounts.push(BulkDiscount {
+            min_packages,
+            percent_off,
+        });
+        self.discounts.sort_by_key(|discount| discount.min_packages);
+        Ok(self)
+    }
+
+    fn add(&mut self, package: Package) {
+        self.packages.push(package);
+    }
+
+    // Groups packages by route in the order the routes first appear, then
+    // splits every group into consignments that stay below the weight cap.
+    fn consignments(&self) -> Result<Vec<Vec<&Package>>, ShipmentError> {
+        let mut groups: Vec<Vec<&Package>> = Vec::new();
+        for package in &self.packages {
+            let group = groups.iter_mut().find(|group| {
+                group[0].sender_country == package.sender_country
+                    && group[0].recipient_country == package.recipient_country
+            });
+            match group {
+                Some(group) => group.push(package),
+                None => groups.push(vec![package]),
+            }
+        }
+
+        let mut consignments = Vec::new();
+        for group in groups {
+            let mut consignment = Vec::new();
+            let mut weight_in_grams: u32 = 0;
+            for package in group {
+                if package.weight_in_grams > self.cap_in_grams {
+                    return Err(ShipmentError::OverWeightCap {
+                        weight_in_grams: package.weight_in_grams,
+                        cap_in_grams: self.cap_in_grams,
+                    });
+                }
+                let fits = weight_in_grams
+                    .checked_add(package.weight_in_grams)
+                    .is_some_and(|total| total <= self.cap_in_grams);
+                if !fits {
+                    consignments.push(consignment);
+                    consignment = Vec::new();
+                    weight_in_grams = 0;
+                }
+                weight_in_grams += package.weight_in_grams;
+                consignment.push(package);
+            }
+            consignments.push(consignment);
+        }
+
+        Ok(consignments)
+    }
+
+    fn percent_off(&self, package_count: usize) -> u32 {
+        self.discounts
+            .iter()
+            .rev()
+            .find(|discount| discount.min_packages <= package_count)
+            .map_or(0, |discount| discount.percent_off)
+    }
+
+    fn quote(&self, tariffs: &TariffTable) -> Result<ShipmentQuote, ShipmentError> {
+        let mut consignments = Vec::new();
+        let mut total: u32 = 0;
+
+        for packages in self.consignments()? {
+            let mut lines = Vec::new();
+            let mut subtotal: u32 = 0;
+            for package in &packages {
+                let fees = package.get_fees(tariffs)?;
+                subtotal = subtotal.checked_add(fees).ok_or(FeeError::Overflow)?;
+                lines.push(QuoteLine {
+                    weight_in_grams: package.weight_in_grams,
+                    fees,
+                });
+            }
+
+            // The discount is at most the subtotal, so it fits in a `u32`.
+            let percent_off = self.percent_off(packages.len());
+            let discount = (u64::from(subtotal) * u64::from(percent_off) / 100) as u32;
+            let consignment_total = subtotal - discount;
+            total = total
+                .checked_add(consignment_total)
+                .ok_or(FeeError::Overflow)?;
+
+            consignments.push(ConsignmentQuote {
+                sender_country: packages[0].sender_country.clone(),
+                recipient_country: packages[0].recipient_country.clone(),
+                total_weight_in_grams: lines.iter().map(|line| line.weight_in_grams).sum(),
+                lines,
+                subtotal,
+                discount,
+                total: consignment_total,
+            });
+        }
+
+        Ok(ShipmentQuote {
+            consignments,
+            total,
+        })
     }
 }
 
@@ -48,6 +797,66 @@ mod tests {
         Package::new(sender_country, recipient_country, 5);
     }
 
+    #[test]
+    fn fail_trying_weightless_package() {
+        let sender_country = String::from("Spain");
+        let recipient_country = String::from("Austria");
+
+        let result = Package::try_new(sender_country, recipient_country, 5);
+
+        assert_eq!(result.unwrap_err(), PackageError::TooLight(5));
+    }
+
+    #[test]
+    fn fail_trying_overweight_package() {
+        let sender_country = String::from("Spain");
+        let recipient_country = String::from("Austria");
+
+        let result = Package::try_new(sender_country, recipient_country, 30_001);
+
+        assert_eq!(result.unwrap_err(), PackageError::TooHeavy(30_001));
+    }
+
+    #[test]
+    fn fail_trying_empty_countries() {
+        assert_eq!(
+            Package::try_new(String::new(), String::from("Austria"), 100).unwrap_err(),
+            PackageError::EmptySenderCountry,
+        );
+        assert_eq!(
+            Package::try_new(String::from("Spain"), String::new(), 100).unwrap_err(),
+            PackageError::EmptyRecipientCountry,
+        );
+    }
+
+    #[test]
+    fn fail_trying_invalid_country() {
+        assert_eq!(
+            Package::try_new(String::from(" Spain"), String::from("Austria"), 100).unwrap_err(),
+            PackageError::InvalidCountry(String::from(" Spain")),
+        );
+        assert_eq!(
+            Package::try_new(String::from("Spain"), String::from("Austria42"), 100).unwrap_err(),
+            PackageError::InvalidCountry(String::from("Austria42")),
+        );
+        assert!(Package::try_new(
+            String::from("Côte d'Ivoire"),
+            String::from("St. Lucia"),
+            100
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn package_error_messages() {
+        assert_eq!(
+            PackageError::TooLight(5).to_string(),
+            "Can't ship a package with weight below 10 grams",
+        );
+        let err: Box<dyn Error> = Box::new(PackageError::EmptySenderCountry);
+        assert_eq!(err.to_string(), "sender country is empty");
+    }
+
     #[test]
     fn create_international_package() {
         let sender_country = String::from("Spain");
@@ -77,7 +886,505 @@ mod tests {
 
         let package = Package::new(sender_country, recipient_country, 1500);
 
-        assert_eq!(package.get_fees(cents_per_gram), 4500);
-        assert_eq!(package.get_fees(cents_per_gram * 2), 9000);
+        let tariffs = TariffTable::new(Tariff::new(cents_per_gram));
+        assert_eq!(package.get_fees(&tariffs), Ok(4500));
+        let tariffs = TariffTable::new(Tariff::new(cents_per_gram * 2));
+        assert_eq!(package.get_fees(&tariffs), Ok(9000));
+    }
+
+    fn europe_tariffs() -> TariffTable {
+        TariffTable::new(Tariff::new(1).with_minimum_charge(500))
+            .with_zone("Austria", "EU")
+            .with_zone("France", "EU")
+            .with_zone_tariff("EU", Tariff::new(4).with_bracket(5000, 3))
+            .with_route("Spain", "France", Tariff::new(2))
+    }
+
+    #[test]
+    fn calculate_domestic_minimum_charge() {
+        let package = Package::new(String::from("Spain"), String::from("Spain"), 100);
+
+        assert_eq!(package.get_fees(&europe_tariffs()), Ok(500));
+    }
+
+    #[test]
+    fn calculate_zone_fees_with_weight_brackets() {
+        let tariffs = europe_tariffs();
+
+        let light = Package::new(String::from("Spain"), String::from("Austria"), 1000);
+        assert_eq!(light.get_fees(&tariffs), Ok(4000));
+
+        let heavy = Package::new(String::from("Spain"), String::from("Austria"), 6000);
+        assert_eq!(heavy.get_fees(&tariffs), Ok(18000));
+    }
+
+    #[test]
+    fn route_tariff_takes_precedence_over_zone() {
+        let package = Package::new(String::from("Spain"), String::from("France"), 1000);
+
+        assert_eq!(package.get_fees(&europe_tariffs()), Ok(2000));
+    }
+
+    #[test]
+    fn fail_calculating_fees_without_tariff() {
+        let package = Package::new(String::from("Spain"), String::from("Russia"), 1000);
+
+        assert_eq!(
+            package.get_fees(&europe_tariffs()),
+            Err(FeeError::NoTariff {
+                sender_country: String::from("Spain"),
+                recipient_country: String::from("Russia"),
+            }),
+        );
+    }
+
+    #[test]
+    fn small_dense_package_bills_actual_weight() {
+        // 10 * 10 * 10 cm³ / 5000 is 200 grams of dimensional weight.
+        let package = Package::new(String::from("Spain"), String::from("Spain"), 2000)
+            .with_dimensions(Dimensions {
+                length_in_cm: 10,
+                width_in_cm: 10,
+                height_in_cm: 10,
+            })
+            .unwrap();
+
+        assert_eq!(package.volumetric_weight(), Some(200));
+        assert_eq!(package.billable_weight(), 2000);
+        let tariffs = TariffTable::new(Tariff::new(3));
+        assert_eq!(package.get_fees(&tariffs), Ok(6000));
+    }
+
+    #[test]
+    fn large_light_package_bills_volumetric_weight() {
+        // 60 * 40 * 40 cm³ / 5000 is 19200 grams of dimensional weight.
+        let package = Package::new(String::from("Spain"), String::from("Spain"), 1500)
+            .with_dimensions(Dimensions {
+                length_in_cm: 60,
+                width_in_cm: 40,
+                height_in_cm: 40,
+            })
+            .unwrap();
+
+        assert_eq!(package.billable_weight(), 19200);
+        let tariffs = TariffTable::new(Tariff::new(3));
+        assert_eq!(package.get_fees(&tariffs), Ok(57600));
+
+        let package = package.with_volumetric_divisor(6000).unwrap();
+        assert_eq!(package.billable_weight(), 16000);
+    }
+
+    #[test]
+    fn volumetric_weight_rounds_up() {
+        let package = Package::new(String::from("Spain"), String::from("Spain"), 10)
+            .with_dimensions(Dimensions {
+                length_in_cm: 1,
+                width_in_cm: 1,
+                height_in_cm: 7,
+            })
+            .unwrap();
+
+        // 7000 / 5000 = 1.4 grams
+        assert_eq!(package.volumetric_weight(), Some(2));
+    }
+
+    #[test]
+    fn package_without_dimensions_bills_actual_weight() {
+        let package = Package::new(String::from("Spain"), String::from("Spain"), 1500);
+
+        assert_eq!(package.volumetric_weight(), None);
+        assert_eq!(package.billable_weight(), 1500);
+    }
+
+    #[test]
+    fn fail_setting_invalid_dimensions() {
+        let dimensions = Dimensions {
+            length_in_cm: 10,
+            width_in_cm: 0,
+            height_in_cm: 10,
+        };
+        let package = Package::new(String::from("Spain"), String::from("Spain"), 1500);
+        assert_eq!(
+            package.with_dimensions(dimensions).unwrap_err(),
+            PackageError::InvalidDimensions(dimensions),
+        );
+
+        let package = Package::new(String::from("Spain"), String::from("Spain"), 1500);
+        assert_eq!(
+            package.with_volumetric_divisor(0).unwrap_err(),
+            PackageError::ZeroVolumetricDivisor,
+        );
+    }
+
+    #[test]
+    fn fail_calculating_overflowing_fees() {
+        let package = Package::new(String::from("Spain"), String::from("Spain"), 30_000);
+        let tariffs = TariffTable::new(Tariff::new(u32::MAX / 1000));
+
+        assert_eq!(package.get_fees(&tariffs), Err(FeeError::Overflow));
+    }
+
+    fn declaration(items: &[(&str, u32, u32, &str)]) -> CustomsDeclaration {
+        CustomsDeclaration {
+            items: items
+                .iter()
+                .map(
+                    |&(description, quantity, value_in_cents, origin_country)| DeclaredItem {
+                        description: String::from(description),
+                        quantity,
+                        value_in_cents,
+                        origin_country: String::from(origin_country),
+                    },
+                )
+                .collect(),
+        }
+    }
+
+    fn duties() -> DutyTable {
+        DutyTable::new().with_rate("Austria", 15000, 20)
+    }
+
+    #[test]
+    fn charge_duty_above_threshold() {
+        let package = Package::new(String::from("Spain"), String::from("Austria"), 1000)
+            .with_customs_declaration(declaration(&[
+                ("Books", 4, 2500, "Spain"),
+                ("Wine", 2, 5000, "Spain"),
+            ]))
+            .unwrap();
+
+        assert_eq!(
+            package.get_charges(&europe_tariffs(), &duties()),
+            Ok(Charges {
+                transport: 4000,
+                duty: 4000,
+                total: 8000,
+            }),
+        );
+    }
+
+    #[test]
+    fn exempt_returned_goods_when_enabled() {
+        let package = Package::new(String::from("Spain"), String::from("Austria"), 1000)
+            .with_customs_declaration(declaration(&[
+                ("Books", 4, 2500, "Spain"),
+                ("Lederhosen", 1, 20000, "Austria"),
+            ]))
+            .unwrap();
+
+        let charges = package.get_charges(&europe_tariffs(), &duties()).unwrap();
+        assert_eq!(charges.duty, 6000);
+
+        let duties = duties().with_returned_goods_exempt();
+        let charges = package.get_charges(&europe_tariffs(), &duties).unwrap();
+        // Only the books count, and they're below the threshold.
+        assert_eq!(charges.duty, 0);
+    }
+
+    #[test]
+    fn skip_duty_below_threshold() {
+        let package = Package::new(String::from("Spain"), String::from("Austria"), 1000)
+            .with_customs_declaration(declaration(&[("Books", 2, 2500, "Spain")]))
+            .unwrap();
+
+        let charges = package.get_charges(&europe_tariffs(), &duties()).unwrap();
+        assert_eq!(charges.duty, 0);
+        assert_eq!(charges.total, charges.transport);
+    }
+
+    #[test]
+    fn domestic_packages_pay_no_duty() {
+        let package = Package::new(String::from("Spain"), String::from("Spain"), 1000);
+
+        let charges = package.get_charges(&europe_tariffs(), &duties()).unwrap();
+        assert_eq!(charges.duty, 0);
+    }
+
+    #[test]
+    fn fail_declaring_domestic_package() {
+        let package = Package::new(String::from("Spain"), String::from("Spain"), 1000);
+
+        assert_eq!(
+            package
+                .with_customs_declaration(declaration(&[("Books", 2, 2500, "Spain")]))
+                .unwrap_err(),
+            PackageError::DomesticCustomsDeclaration,
+        );
+    }
+
+    #[test]
+    fn fail_declaring_item_without_description() {
+        let package = Package::new(String::from("Spain"), String::from("Austria"), 1000);
+
+        assert_eq!(
+            package
+                .with_customs_declaration(declaration(&[(" ", 2, 2500, "Spain")]))
+                .unwrap_err(),
+            PackageError::EmptyItemDescription,
+        );
+    }
+
+    #[test]
+    fn fail_charging_international_package_without_declaration() {
+        let package = Package::new(String::from("Spain"), String::from("Austria"), 1000);
+
+        assert_eq!(
+            package.get_charges(&europe_tariffs(), &duties()),
+            Err(FeeError::MissingCustomsDeclaration),
+        );
+    }
+
+    #[test]
+    fn fail_charging_without_duty_rate() {
+        let package = Package::new(String::from("Spain"), String::from("France"), 1000)
+            .with_customs_declaration(declaration(&[("Books", 2, 2500, "Spain")]))
+            .unwrap();
+
+        assert_eq!(
+            package.get_charges(&europe_tariffs(), &duties()),
+            Err(FeeError::NoDutyRate(String::from("France"))),
+        );
+    }
+
+    fn package(sender_country: &str, recipient_country: &str, weight_in_grams: u32) -> Package {
+        Package::new(
+            String::from(sender_country),
+            String::from(recipient_country),
+            weight_in_grams,
+        )
+    }
+
+    #[test]
+    fn quote_groups_packages_by_route() {
+        let mut shipment = Shipment::new(30_000);
+        shipment.add(package("Spain", "Spain", 1000));
+        shipment.add(package("Spain", "Austria", 1000));
+        shipment.add(package("Spain", "Spain", 2000));
+
+        let quote = shipment.quote(&europe_tariffs()).unwrap();
+
+        assert_eq!(quote.consignments.len(), 2);
+        let domestic = &quote.consignments[0];
+        assert_eq!(domestic.recipient_country, "Spain");
+        assert_eq!(
+            domestic.lines,
+            [
+                QuoteLine {
+                    weight_in_grams: 1000,
+                    fees: 1000,
+                },
+                QuoteLine {
+                    weight_in_grams: 2000,
+                    fees: 2000,
+                },
+            ],
+        );
+        assert_eq!(domestic.total_weight_in_grams, 3000);
+        assert_eq!(domestic.total, 3000);
+        assert_eq!(quote.consignments[1].recipient_country, "Austria");
+        assert_eq!(quote.consignments[1].total, 4000);
+        assert_eq!(quote.total, 7000);
+    }
+
+    #[test]
+    fn quote_applies_bulk_discounts() {
+        let mut shipment = Shipment::new(30_000)
+            .with_bulk_discount(2, 10)
+            .unwrap()
+            .with_bulk_discount(3, 20)
+            .unwrap();
+        for _ in 0..3 {
+            shipment.add(package("Spain", "Spain", 1000));
+        }
+        shipment.add(package("Spain", "Austria", 1000));
+
+        let quote = shipment.quote(&europe_tariffs()).unwrap();
+
+        let domestic = &quote.consignments[0];
+        assert_eq!(domestic.subtotal, 3000);
+        assert_eq!(domestic.discount, 600);
+        assert_eq!(domestic.total, 2400);
+        assert_eq!(quote.consignments[1].discount, 0);
+        assert_eq!(quote.total, 6400);
+    }
+
+    #[test]
+    fn quote_splits_consignments_over_weight_cap() {
+        let mut shipment = Shipment::new(5000);
+        shipment.add(package("Spain", "Spain", 3000));
+        shipment.add(package("Spain", "Spain", 2000));
+        shipment.add(package("Spain", "Spain", 1000));
+
+        let quote = shipment.quote(&europe_tariffs()).unwrap();
+
+        let weights: Vec<u32> = quote
+            .consignments
+            .iter()
+            .map(|consignment| consignment.total_weight_in_grams)
+            .collect();
+        assert_eq!(weights, [5000, 1000]);
+        assert_eq!(quote.total, 6000);
+    }
+
+    #[test]
+    fn split_consignments_near_u32_max() {
+        let cap_in_grams = u32::MAX - 10_000;
+        let weight_in_grams = Package::MAX_WEIGHT_IN_GRAMS;
+        let per_consignment = (cap_in_grams / weight_in_grams) as usize;
+        let mut shipment = Shipment::new(cap_in_grams);
+        for _ in 0..=per_consignment {
+            shipment.add(package("Spain", "Spain", weight_in_grams));
+        }
+
+        let sizes: Vec<usize> = shipment
+            .consignments()
+            .unwrap()
+            .iter()
+            .map(Vec::len)
+            .collect();
+        assert_eq!(sizes, [per_consignment, 1]);
+    }
+
+    #[test]
+    fn fail_quoting_package_over_weight_cap() {
+        let mut shipment = Shipment::new(1000);
+        shipment.add(package("Spain", "Spain", 2000));
+
+        assert_eq!(
+            shipment.quote(&europe_tariffs()),
+            Err(ShipmentError::OverWeightCap {
+                weight_in_grams: 2000,
+                cap_in_grams: 1000,
+            }),
+        );
+    }
+
+    #[test]
+    fn fail_quoting_route_without_tariff() {
+        let mut shipment = Shipment::new(30_000);
+        shipment.add(package("Spain", "Russia", 2000));
+
+        assert!(matches!(
+            shipment.quote(&europe_tariffs()),
+            Err(ShipmentError::Fee(FeeError::NoTariff { .. })),
+        ));
+    }
+
+    #[test]
+    fn fail_adding_discount_over_100_percent() {
+        assert_eq!(
+            Shipment::new(30_000)
+                .with_bulk_discount(2, 101)
+                .unwrap_err(),
+            ShipmentError::InvalidDiscount(101),
+        );
+    }
+
+    fn hours(n: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(n * 3600)
+    }
+
+    #[test]
+    fn track_package_until_delivered() {
+        let mut package = package("Spain", "Austria", 1000);
+        assert_eq!(package.status(), Status::Created);
+        assert_eq!(package.time_in_transit(hours(1)), None);
+
+        package.transition(Status::Dispatched, hours(1)).unwrap();
+        package.transition(Status::InTransit, hours(2)).unwrap();
+        assert_eq!(
+            package.time_in_transit(hours(5)),
+            Some(Duration::from_secs(4 * 3600))
+        );
+
+        package
+            .transition(Status::OutForDelivery, hours(20))
+            .unwrap();
+        package.transition(Status::Delivered, hours(25)).unwrap();
+
+        assert_eq!(package.status(), Status::Delivered);
+        assert_eq!(
+            package.time_in_transit(hours(100)),
+            Some(Duration::from_secs(24 * 3600))
+        );
+        assert_eq!(
+            package.history(),
+            [
+                TrackingEvent {
+                    status: Status::Dispatched,
+                    at: hours(1),
+                },
+                TrackingEvent {
+                    status: Status::InTransit,
+                    at: hours(2),
+                },
+                TrackingEvent {
+                    status: Status::OutForDelivery,
+                    at: hours(20),
+                },
+                TrackingEvent {
+                    status: Status::Delivered,
+                    at: hours(25),
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn track_failed_delivery_and_return() {
+        let mut package = package("Spain", "Austria", 1000);
+        package.transition(Status::Dispatched, hours(1)).unwrap();
+        package.transition(Status::InTransit, hours(2)).unwrap();
+        package
+            .transition(Status::OutForDelivery, hours(3))
+            .unwrap();
+        package.transition(Status::InTransit, hours(4)).unwrap();
+        package.transition(Status::Returned, hours(9)).unwrap();
+
+        assert_eq!(package.status(), Status::Returned);
+        assert_eq!(package.history().len(), 5);
+        assert_eq!(
+            package.time_in_transit(hours(100)),
+            Some(Duration::from_secs(8 * 3600))
+        );
+    }
+
+    #[test]
+    fn fail_illegal_transitions() {
+        let mut package = package("Spain", "Austria", 1000);
+
+        assert_eq!(
+            package.transition(Status::Delivered, hours(1)),
+            Err(TrackingError::IllegalTransition {
+                from: Status::Created,
+                to: Status::Delivered,
+            }),
+        );
+
+        package.transition(Status::Dispatched, hours(1)).unwrap();
+        package.transition(Status::Lost, hours(2)).unwrap();
+        assert_eq!(
+            package.transition(Status::InTransit, hours(3)),
+            Err(TrackingError::IllegalTransition {
+                from: Status::Lost,
+                to: Status::InTransit,
+            }),
+        );
+        assert_eq!(package.history().len(), 2);
+    }
+
+    #[test]
+    fn fail_out_of_order_events() {
+        let mut package = package("Spain", "Austria", 1000);
+        package.transition(Status::Dispatched, hours(5)).unwrap();
+
+        assert_eq!(
+            package.transition(Status::InTransit, hours(4)),
+            Err(TrackingError::OutOfOrder {
+                last: hours(5),
+                at: hours(4),
+            }),
+        );
+        assert_eq!(package.status(), Status::Dispatched);
     }
 }
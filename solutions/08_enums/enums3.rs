@@ -1,8 +1,19 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::mem;
+use std::str::FromStr;
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+
+#[derive(Clone, PartialEq, Debug)]
 struct Point {
     x: u64,
     y: u64,
 }
 
+#[derive(Clone, PartialEq, Debug)]
 enum Message {
     Resize { width: u64, height: u64 },
     Move(Point),
@@ -11,6 +22,619 @@ enum Message {
     Quit,
 }
 
+#[derive(PartialEq, Debug)]
+enum ParseMessageErrorKind {
+    Empty,
+    UnknownCommand(String),
+    MissingArgument,
+    UnexpectedArgument,
+    InvalidNumber(String),
+    InvalidColor(String),
+    UnterminatedString,
+    InvalidEscape(char),
+    InvalidValue(String),
+}
+
+// `column` counts characters starting at 1 and points at the offending token.
+#[derive(PartialEq, Debug)]
+struct ParseMessageError {
+    column: usize,
+    kind: ParseMessageErrorKind,
+}
+
+impl fmt::Display for ParseMessageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "column {}: ", self.column)?;
+        match &self.kind {
+            ParseMessageErrorKind::Empty => f.write_str("expected a command"),
+            ParseMessageErrorKind::UnknownCommand(command) => {
+                write!(f, "unknown command {command:?}")
+            }
+            ParseMessageErrorKind::MissingArgument => f.write_str("missing argument"),
+            ParseMessageErrorKind::UnexpectedArgument => f.write_str("unexpected argument"),
+            ParseMessageErrorKind::InvalidNumber(s) => write!(f, "{s:?} isn't a number"),
+            ParseMessageErrorKind::InvalidColor(s) => {
+                write!(f, "{s:?} isn't a color like #ff00ff")
+            }
+            ParseMessageErrorKind::UnterminatedString => f.write_str("unterminated string"),
+            ParseMessageErrorKind::InvalidEscape(c) => write!(f, "invalid escape \\{c}"),
+            ParseMessageErrorKind::InvalidValue(s) => write!(f, "invalid value {s:?}"),
+        }
+    }
+}
+
+impl Error for ParseMessageError {}
+
+// Splits a line into whitespace separated words. Words in double quotes can
+// contain whitespace and the escapes \\, \", \n, \r and \t.
+fn tokenize(s: &str) -> Result<Vec<(usize, String)>, ParseMessageError> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().zip(1..).peekable();
+
+    while let Some(&(c, column)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let mut token = String::new();
+        if c != '"' {
+            while let Some((c, _)) = chars.next_if(|&(c, _)| !c.is_whitespace()) {
+                token.push(c);
+            }
+            tokens.push((column, token));
+            continue;
+        }
+
+        chars.next();
+        loop {
+            let error = |kind| ParseMessageError { column, kind };
+            match chars.next() {
+                None => return Err(error(ParseMessageErrorKind::UnterminatedString)),
+                Some(('"', _)) => break,
+                Some(('\\', escape_column)) => {
+                    let escaped = match chars.next() {
+                        Some(('\\', _)) => '\\',
+                        Some(('"', _)) => '"',
+                        Some(('n', _)) => '\n',
+                        Some(('r', _)) => '\r',
+                        Some(('t', _)) => '\t',
+                        Some((c, _)) => {
+                            return Err(ParseMessageError {
+                                column: escape_column,
+                                kind: ParseMessageErrorKind::InvalidEscape(c),
+                            })
+                        }
+                        None => return Err(error(ParseMessageErrorKind::UnterminatedString)),
+                    };
+                    token.push(escaped);
+                }
+                Some((c, _)) => token.push(c),
+            }
+        }
+        tokens.push((column, token));
+    }
+
+    Ok(tokens)
+}
+
+fn parse_color(s: &str) -> Option<(u8, u8, u8)> {
+    let hex = s.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+// Hands out the arguments of a tokenized line, with errors pointing at them.
+struct Arguments<'a> {
+    tokens: std::slice::Iter<'a, (usize, String)>,
+    // The column right after the end of the line.
+    end: usize,
+}
+
+impl<'a> Arguments<'a> {
+    fn new(tokens: &'a [(usize, String)], line: &str) -> Self {
+        Self {
+            tokens: tokens.iter(),
+            end: line.chars().count() + 1,
+        }
+    }
+
+    fn command(&mut self) -> Result<&'a (usize, String), ParseMessageError> {
+        self.tokens.next().ok_or(ParseMessageError {
+            column: self.end,
+            kind: ParseMessageErrorKind::Empty,
+        })
+    }
+
+    fn next(&mut self) -> Result<&'a (usize, String), ParseMessageError> {
+        self.tokens.next().ok_or(ParseMessageError {
+            column: self.end,
+            kind: ParseMessageErrorKind::MissingArgument,
+        })
+    }
+
+    fn parse_with<T>(
+        &mut self,
+        parse: impl FnOnce(&str) -> Option<T>,
+        kind: impl FnOnce(String) -> ParseMessageErrorKind,
+    ) -> Result<T, ParseMessageError> {
+        let (column, arg) = self.next()?;
+        parse(arg).ok_or_else(|| ParseMessageError {
+            column: *column,
+            kind: kind(arg.clone()),
+        })
+    }
+
+    fn number<T: FromStr>(&mut self) -> Result<T, ParseMessageError> {
+        self.parse_with(|arg| arg.parse().ok(), ParseMessageErrorKind::InvalidNumber)
+    }
+
+    fn color(&mut self) -> Result<(u8, u8, u8), ParseMessageError> {
+        self.parse_with(parse_color, ParseMessageErrorKind::InvalidColor)
+    }
+
+    fn finish(mut self) -> Result<(), ParseMessageError> {
+        match self.tokens.next() {
+            Some((column, _)) => Err(ParseMessageError {
+                column: *column,
+                kind: ParseMessageErrorKind::UnexpectedArgument,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+fn unknown_command((column, command): &(usize, String)) -> ParseMessageError {
+    ParseMessageError {
+        column: *column,
+        kind: ParseMessageErrorKind::UnknownCommand(command.clone()),
+    }
+}
+
+// Parses the syntax that `Display` produces, for example `resize 10 30`,
+// `move 10 15`, `echo "hello world"`, `color #ff00ff` or `quit`.
+impl FromStr for Message {
+    type Err = ParseMessageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut args = Arguments::new(&tokens, s);
+
+        let command = args.command()?;
+        let message = match command.1.as_str() {
+            "resize" => Message::Resize {
+                width: args.number()?,
+                height: args.number()?,
+            },
+            "move" => Message::Move(Point {
+                x: args.number()?,
+                y: args.number()?,
+            }),
+            "echo" => Message::Echo(args.next()?.1.clone()),
+            "color" => {
+                let (r, g, b) = args.color()?;
+                Message::ChangeColor(r, g, b)
+            }
+            "quit" => Message::Quit,
+            _ => return Err(unknown_command(command)),
+        };
+        args.finish()?;
+
+        Ok(message)
+    }
+}
+
+fn write_quoted(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in s.chars() {
+        match c {
+            '\\' => f.write_str("\\\\")?,
+            '"' => f.write_str("\\\"")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    f.write_str("\"")
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Message::Resize { width, height } => write!(f, "resize {width} {height}"),
+            Message::Move(Point { x, y }) => write!(f, "move {x} {y}"),
+            Message::Echo(s) => {
+                f.write_str("echo ")?;
+                write_quoted(f, s)
+            }
+            Message::ChangeColor(r, g, b) => write!(f, "color #{r:02x}{g:02x}{b:02x}"),
+            Message::Quit => f.write_str("quit"),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum DecodeError {
+    Io(io::Error),
+    Truncated,
+    UnknownTag(u8),
+    VarintOverflow,
+    InvalidUtf8(std::string::FromUtf8Error),
+}
+
+impl From<io::Error> for DecodeError {
+    fn from(err: io::Error) -> Self {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            Self::Truncated
+        } else {
+            Self::Io(err)
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Io(err) => write!(f, "failed to read message: {err}"),
+            DecodeError::Truncated => f.write_str("message is truncated"),
+            DecodeError::UnknownTag(tag) => write!(f, "unknown message tag {tag}"),
+            DecodeError::VarintOverflow => f.write_str("varint doesn't fit in a `u64`"),
+            DecodeError::InvalidUtf8(err) => write!(f, "echo isn't valid UTF-8: {err}"),
+        }
+    }
+}
+
+impl Error for DecodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DecodeError::Io(err) => Some(err),
+            DecodeError::InvalidUtf8(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+// LEB128: 7 bits per byte, least significant group first, the high bit is set
+// on every byte except the last one.
+fn write_varint(writer: &mut impl Write, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return writer.write_all(&[byte]);
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_u8(reader: &mut impl Read) -> Result<u8, DecodeError> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_varint(reader: &mut impl Read) -> Result<u64, DecodeError> {
+    let mut value: u64 = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = read_u8(reader)?;
+        let bits = u64::from(byte & 0x7f);
+        // The 10th byte may only contribute the single highest bit.
+        if shift == 63 && bits > 1 {
+            return Err(DecodeError::VarintOverflow);
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(DecodeError::VarintOverflow)
+}
+
+impl Message {
+    const TAG_RESIZE: u8 = 0;
+    const TAG_MOVE: u8 = 1;
+    const TAG_ECHO: u8 = 2;
+    const TAG_CHANGE_COLOR: u8 = 3;
+    const TAG_QUIT: u8 = 4;
+
+    // A tag byte followed by the fields: varints for numbers, a varint length
+    // and UTF-8 bytes for strings and raw bytes for colors.
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        match self {
+            Message::Resize { width, height } => {
+                writer.write_all(&[Self::TAG_RESIZE])?;
+                write_varint(writer, *width)?;
+                write_varint(writer, *height)
+            }
+            Message::Move(Point { x, y }) => {
+                writer.write_all(&[Self::TAG_MOVE])?;
+                write_varint(writer, *x)?;
+                write_varint(writer, *y)
+            }
+            Message::Echo(s) => {
+                writer.write_all(&[Self::TAG_ECHO])?;
+                write_varint(writer, s.len() as u64)?;
+                writer.write_all(s.as_bytes())
+            }
+            Message::ChangeColor(r, g, b) => {
+                writer.write_all(&[Self::TAG_CHANGE_COLOR, *r, *g, *b])
+            }
+            Message::Quit => writer.write_all(&[Self::TAG_QUIT]),
+        }
+    }
+
+    fn decode(reader: &mut impl Read) -> Result<Self, DecodeError> {
+        let message = match read_u8(reader)? {
+            Self::TAG_RESIZE => Message::Resize {
+                width: read_varint(reader)?,
+                height: read_varint(reader)?,
+            },
+            Self::TAG_MOVE => Message::Move(Point {
+                x: read_varint(reader)?,
+                y: read_varint(reader)?,
+            }),
+            Self::TAG_ECHO => {
+                let len = read_varint(reader)?;
+                // Don't trust the length enough to allocate it up front.
+                let mut bytes = Vec::new();
+                reader.take(len).read_to_end(&mut bytes)?;
+                if (bytes.len() as u64) < len {
+                    return Err(DecodeError::Truncated);
+                }
+                Message::Echo(String::from_utf8(bytes).map_err(DecodeError::InvalidUtf8)?)
+            }
+            Self::TAG_CHANGE_COLOR => {
+                let mut rgb = [0; 3];
+                reader.read_exact(&mut rgb)?;
+                Message::ChangeColor(rgb[0], rgb[1], rgb[2])
+            }
+            Self::TAG_QUIT => Message::Quit,
+            tag => return Err(DecodeError::UnknownTag(tag)),
+        };
+
+        Ok(message)
+    }
+}
+
+// The new value of the part of `State` that a message changes. Applying a
+// change returns the old value, which is the change that undoes it.
+#[derive(Clone, PartialEq, Debug)]
+enum Change {
+    Size { width: u64, height: u64 },
+    Position(Point),
+    Message(String),
+    Color(u8, u8, u8),
+    Quit(bool),
+}
+
+impl From<Message> for Change {
+    fn from(message: Message) -> Self {
+        match message {
+            Message::Resize { width, height } => Change::Size { width, height },
+            Message::Move(point) => Change::Position(point),
+            Message::Echo(s) => Change::Message(s),
+            Message::ChangeColor(r, g, b) => Change::Color(r, g, b),
+            Message::Quit => Change::Quit(true),
+        }
+    }
+}
+
+// What happens to positions outside of the canvas. A position is inside if
+// `x < width` and `y < height`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum BoundsPolicy {
+    Clamp,
+    Reject,
+    Wrap,
+}
+
+#[derive(PartialEq, Debug)]
+enum BoundsError {
+    OutOfBounds {
+        point: Point,
+        width: u64,
+        height: u64,
+    },
+    EmptyCanvas,
+}
+
+impl fmt::Display for BoundsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BoundsError::OutOfBounds {
+                point,
+                width,
+                height,
+            } => write!(
+                f,
+                "({}, {}) is outside of the {width}x{height} canvas",
+                point.x, point.y,
+            ),
+            BoundsError::EmptyCanvas => f.write_str("the canvas has no room for a position"),
+        }
+    }
+}
+
+impl Error for BoundsError {}
+
+#[derive(PartialEq, Debug)]
+struct ParseBoundsPolicyError(String);
+
+impl fmt::Display for ParseBoundsPolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown bounds policy {:?}", self.0)
+    }
+}
+
+impl Error for ParseBoundsPolicyError {}
+
+impl FromStr for BoundsPolicy {
+    type Err = ParseBoundsPolicyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clamp" => Ok(BoundsPolicy::Clamp),
+            "reject" => Ok(BoundsPolicy::Reject),
+            "wrap" => Ok(BoundsPolicy::Wrap),
+            _ => Err(ParseBoundsPolicyError(s.to_string())),
+        }
+    }
+}
+
+impl BoundsPolicy {
+    fn name(self) -> &'static str {
+        match self {
+            BoundsPolicy::Clamp => "clamp",
+            BoundsPolicy::Reject => "reject",
+            BoundsPolicy::Wrap => "wrap",
+        }
+    }
+
+    // `width` and `height` must not be 0.
+    fn fit(self, point: &Point, width: u64, height: u64) -> Result<Point, BoundsError> {
+        if point.x < width && point.y < height {
+            return Ok(point.clone());
+        }
+
+        match self {
+            BoundsPolicy::Clamp => Ok(Point {
+                x: point.x.min(width - 1),
+                y: point.y.min(height - 1),
+            }),
+            BoundsPolicy::Reject => Err(BoundsError::OutOfBounds {
+                point: point.clone(),
+                width,
+                height,
+            }),
+            BoundsPolicy::Wrap => Ok(Point {
+                x: point.x % width,
+                y: point.y % height,
+            }),
+        }
+    }
+}
+
+// Everything that decides how the state reacts to the next message, without
+// its undo history and listeners. Its text form is a single line:
+//
+//     snapshot <width> <height> <x> <y> <color> <quit> <bounds policy> <message>
+//
+// where the bounds policy is `none` if the state doesn't have one.
+#[derive(Clone, PartialEq, Debug)]
+struct Snapshot {
+    width: u64,
+    height: u64,
+    position: Point,
+    message: String,
+    color: (u8, u8, u8),
+    quit: bool,
+    bounds_policy: Option<BoundsPolicy>,
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (r, g, b) = self.color;
+        write!(
+            f,
+            "snapshot {} {} {} {} #{r:02x}{g:02x}{b:02x} {} {} ",
+            self.width,
+            self.height,
+            self.position.x,
+            self.position.y,
+            self.quit,
+            self.bounds_policy.map_or("none", BoundsPolicy::name),
+        )?;
+        write_quoted(f, &self.message)
+    }
+}
+
+impl FromStr for Snapshot {
+    type Err = ParseMessageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut args = Arguments::new(&tokens, s);
+
+        let command = args.command()?;
+        if command.1 != "snapshot" {
+            return Err(unknown_command(command));
+        }
+        let snapshot = Snapshot {
+            width: args.number()?,
+            height: args.number()?,
+            position: Point {
+                x: args.number()?,
+                y: args.number()?,
+            },
+            color: args.color()?,
+            quit: args.parse_with(|arg| arg.parse().ok(), ParseMessageErrorKind::InvalidValue)?,
+            bounds_policy: args.parse_with(
+                |arg| match arg {
+                    "none" => Some(None),
+                    _ => arg.parse().ok().map(Some),
+                },
+                ParseMessageErrorKind::InvalidValue,
+            )?,
+            message: args.next()?.1.clone(),
+        };
+        args.finish()?;
+
+        Ok(snapshot)
+    }
+}
+
+// Appends every processed message to a session log, one per line, plus a
+// snapshot every `snapshot_every` messages and whenever the state changes
+// without a message (undo, redo or a new bounds policy).
+struct Recorder {
+    writer: Box<dyn Write + Send>,
+    snapshot_every: usize,
+    messages: usize,
+    // Recording stops at the first error, `stop_recording` returns it.
+    error: Option<io::Error>,
+}
+
+impl Recorder {
+    fn write(&mut self, line: &dyn fmt::Display) {
+        if self.error.is_none() {
+            if let Err(err) = writeln!(self.writer, "{line}") {
+                self.error = Some(err);
+            }
+        }
+    }
+}
+
+// A message can change more than one part of the state, for example when a
+// resize clamps the position. `redo` is applied in order, `undo` in reverse.
+#[derive(Debug)]
+struct Edit {
+    message: Message,
+    redo: Vec<Change>,
+    undo: Vec<Change>,
+}
+
+// One part of the state that a message changed. For example, after resizing
+// from 10x30 to 20x40, `before` is `Change::Size { width: 10, height: 30 }`
+// and `after` is `Change::Size { width: 20, height: 40 }`. Listeners get one
+// `StateChange` per changed part, so a single message can send several, like
+// a resize that also clamps the position.
+#[derive(PartialEq, Debug)]
+struct StateChange {
+    before: Change,
+    after: Change,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct ListenerHandle(u64);
+
+type Listener = Box<dyn FnMut(&StateChange) + Send>;
+
 struct State {
     width: u64,
     height: u64,
@@ -18,9 +642,40 @@ struct State {
     message: String,
     color: (u8, u8, u8),
     quit: bool,
+    // The oldest edits are dropped once there are more than `history_depth`.
+    history_depth: usize,
+    undo_stack: VecDeque<Edit>,
+    redo_stack: Vec<Edit>,
+    // Without a policy, any position is accepted.
+    bounds_policy: Option<BoundsPolicy>,
+    listeners: Vec<(ListenerHandle, Listener)>,
+    next_listener: u64,
+    recorder: Option<Recorder>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            position: Point { x: 0, y: 0 },
+            message: String::new(),
+            color: (0, 0, 0),
+            quit: false,
+            history_depth: Self::DEFAULT_HISTORY_DEPTH,
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            bounds_policy: None,
+            listeners: Vec::new(),
+            next_listener: 0,
+            recorder: None,
+        }
+    }
 }
 
 impl State {
+    const DEFAULT_HISTORY_DEPTH: usize = 100;
+
     fn resize(&mut self, width: u64, height: u64) {
         self.width = width;
         self.height = height;
@@ -42,15 +697,484 @@ impl State {
         self.quit = true;
     }
 
-    fn process(&mut self, message: Message) {
+    fn apply(&mut self, change: Change) -> Change {
+        match change {
+            Change::Size { width, height } => {
+                let old = Change::Size {
+                    width: self.width,
+                    height: self.height,
+                };
+                self.resize(width, height);
+                old
+            }
+            Change::Position(point) => {
+                let old = Change::Position(self.position.clone());
+                self.move_position(point);
+                old
+            }
+            Change::Message(s) => {
+                let old = Change::Message(mem::take(&mut self.message));
+                self.echo(s);
+                old
+            }
+            Change::Color(r, g, b) => {
+                let (old_r, old_g, old_b) = self.color;
+                self.change_color(r, g, b);
+                Change::Color(old_r, old_g, old_b)
+            }
+            Change::Quit(quit) => {
+                let old = Change::Quit(self.quit);
+                if quit {
+                    self.quit();
+                } else {
+                    // Only undoing a `Quit` can get here.
+                    self.quit = false;
+                }
+                old
+            }
+        }
+    }
+
+    // Returns what each change replaced.
+    fn apply_all(&mut self, changes: &[Change]) -> Vec<StateChange> {
+        changes
+            .iter()
+            .map(|after| StateChange {
+                before: self.apply(after.clone()),
+                after: after.clone(),
+            })
+            .collect()
+    }
+
+    fn record(&mut self, edit: Edit) {
+        if self.history_depth == 0 {
+            return;
+        }
+        if self.undo_stack.len() == self.history_depth {
+            self.undo_stack.pop_front();
+        }
+        self.undo_stack.push_back(edit);
+    }
+
+    fn set_bounds_policy(&mut self, bounds_policy: Option<BoundsPolicy>) {
+        self.bounds_policy = bounds_policy;
+        self.record_snapshot();
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            width: self.width,
+            height: self.height,
+            position: self.position.clone(),
+            message: self.message.clone(),
+            color: self.color,
+            quit: self.quit,
+            bounds_policy: self.bounds_policy,
+        }
+    }
+
+    fn from_snapshot(snapshot: &Snapshot) -> Self {
+        let snapshot = snapshot.clone();
+        Self {
+            width: snapshot.width,
+            height: snapshot.height,
+            position: snapshot.position,
+            message: snapshot.message,
+            color: snapshot.color,
+            quit: snapshot.quit,
+            bounds_policy: snapshot.bounds_policy,
+            ..Default::default()
+        }
+    }
+
+    // The log starts with a snapshot of the current state, so it can be
+    // replayed without knowing what happened before.
+    fn start_recording(&mut self, writer: impl Write + Send + 'static, snapshot_every: usize) {
+        self.recorder = Some(Recorder {
+            writer: Box::new(writer),
+            snapshot_every,
+            messages: 0,
+            error: None,
+        });
+        self.record_snapshot();
+    }
+
+    fn stop_recording(&mut self) -> io::Result<()> {
+        let Some(mut recorder) = self.recorder.take() else {
+            return Ok(());
+        };
+
+        match recorder.error {
+            Some(err) => Err(err),
+            None => recorder.writer.flush(),
+        }
+    }
+
+    fn record_snapshot(&mut self) {
+        let snapshot = self.snapshot();
+        if let Some(recorder) = &mut self.recorder {
+            recorder.write(&snapshot);
+        }
+    }
+
+    fn record_message(&mut self, message: &Message) {
+        let Some(recorder) = &mut self.recorder else {
+            return;
+        };
+
+        recorder.write(message);
+        recorder.messages += 1;
+        if recorder.snapshot_every != 0 && recorder.messages % recorder.snapshot_every == 0 {
+            self.record_snapshot();
+        }
+    }
+
+    // The changes that processing `message` makes under the bounds policy.
+    fn plan(&self, message: &Message) -> Result<Vec<Change>, BoundsError> {
+        let Some(policy) = self.bounds_policy else {
+            return Ok(vec![Change::from(message.clone())]);
+        };
+
         match message {
-            Message::Resize { width, height } => self.resize(width, height),
-            Message::Move(point) => self.move_position(point),
-            Message::Echo(s) => self.echo(s),
-            Message::ChangeColor(r, g, b) => self.change_color(r, g, b),
-            Message::Quit => self.quit(),
+            Message::Move(point) => {
+                if self.width == 0 || self.height == 0 {
+                    return Err(BoundsError::EmptyCanvas);
+                }
+                let point = policy.fit(point, self.width, self.height)?;
+                Ok(vec![Change::Position(point)])
+            }
+            &Message::Resize { width, height } => {
+                if width == 0 || height == 0 {
+                    return Err(BoundsError::EmptyCanvas);
+                }
+                let mut changes = vec![Change::Size { width, height }];
+                let position = policy.fit(&self.position, width, height)?;
+                if position != self.position {
+                    changes.push(Change::Position(position));
+                }
+                Ok(changes)
+            }
+            _ => Ok(vec![Change::from(message.clone())]),
+        }
+    }
+
+    // Listeners are called for every change that a processed, undone or redone
+    // message makes.
+    fn subscribe(&mut self, listener: impl FnMut(&StateChange) + Send + 'static) -> ListenerHandle {
+        let handle = ListenerHandle(self.next_listener);
+        self.next_listener += 1;
+        self.listeners.push((handle, Box::new(listener)));
+        handle
+    }
+
+    // Returns `false` if the listener was already removed.
+    fn unsubscribe(&mut self, handle: ListenerHandle) -> bool {
+        let len = self.listeners.len();
+        self.listeners.retain(|(h, _)| *h != handle);
+        self.listeners.len() != len
+    }
+
+    // Listeners only run once the state is fully updated, so every change
+    // they get is already applied. A panicking listener ends the whole
+    // process, since both profiles build with `panic = "abort"`.
+    fn notify(&mut self, changes: &[StateChange]) {
+        for change in changes {
+            for (_, listener) in &mut self.listeners {
+                listener(change);
+            }
         }
     }
+
+    // Nothing changes if the bounds policy rejects the message.
+    fn try_process(&mut self, message: Message) -> Result<(), BoundsError> {
+        let redo = self.plan(&message)?;
+        let changes = self.apply_all(&redo);
+        let undo = changes.iter().rev().map(|c| c.before.clone()).collect();
+        self.record_message(&message);
+        // A new edit after an undo starts a new branch of history.
+        self.redo_stack.clear();
+        self.record(Edit {
+            message,
+            redo,
+            undo,
+        });
+        self.notify(&changes);
+        Ok(())
+    }
+
+    // Like `try_process`, but messages that the bounds policy rejects are
+    // dropped.
+    fn process(&mut self, message: Message) {
+        let _ = self.try_process(message);
+    }
+
+    // Returns `false` if there is nothing to undo.
+    fn undo(&mut self) -> bool {
+        let Some(edit) = self.undo_stack.pop_back() else {
+            return false;
+        };
+
+        let changes = self.apply_all(&edit.undo);
+        self.record_snapshot();
+        self.redo_stack.push(edit);
+        self.notify(&changes);
+        true
+    }
+
+    // Returns `false` if there is nothing to redo.
+    fn redo(&mut self) -> bool {
+        let Some(edit) = self.redo_stack.pop() else {
+            return false;
+        };
+
+        let changes = self.apply_all(&edit.redo);
+        self.record_snapshot();
+        self.record(edit);
+        self.notify(&changes);
+        true
+    }
+
+    // The messages that can be undone, oldest first.
+    fn history(&self) -> impl Iterator<Item = &Message> {
+        self.undo_stack.iter().map(|edit| &edit.message)
+    }
+
+    fn set_history_depth(&mut self, history_depth: usize) {
+        self.history_depth = history_depth;
+        while self.undo_stack.len() > history_depth {
+            self.undo_stack.pop_front();
+        }
+    }
+}
+
+#[derive(Debug)]
+enum SessionError {
+    Io(io::Error),
+    // `line` starts at 1.
+    Parse {
+        line: usize,
+        error: ParseMessageError,
+    },
+    MissingSnapshot,
+}
+
+impl From<io::Error> for SessionError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionError::Io(err) => write!(f, "failed to read session: {err}"),
+            SessionError::Parse { line, error } => write!(f, "line {line}, {error}"),
+            SessionError::MissingSnapshot => f.write_str("session doesn't start with a snapshot"),
+        }
+    }
+}
+
+impl Error for SessionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SessionError::Io(err) => Some(err),
+            SessionError::Parse { error, .. } => Some(error),
+            SessionError::MissingSnapshot => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum SessionEntry {
+    Message(Message),
+    Snapshot(Snapshot),
+}
+
+// A recorded session log that can rebuild the state after any message.
+#[derive(Debug)]
+struct Session {
+    entries: Vec<SessionEntry>,
+    // Indices into `entries`.
+    messages: Vec<usize>,
+    snapshots: Vec<usize>,
+}
+
+impl Session {
+    fn load(reader: impl BufRead) -> Result<Self, SessionError> {
+        let mut session = Session {
+            entries: Vec::new(),
+            messages: Vec::new(),
+            snapshots: Vec::new(),
+        };
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let error = |error| SessionError::Parse {
+                line: index + 1,
+                error,
+            };
+            let entry = if line.trim_start().starts_with("snapshot") {
+                session.snapshots.push(session.entries.len());
+                SessionEntry::Snapshot(line.parse().map_err(error)?)
+            } else {
+                session.messages.push(session.entries.len());
+                SessionEntry::Message(line.parse().map_err(error)?)
+            };
+            session.entries.push(entry);
+        }
+
+        if session.snapshots.first() != Some(&0) {
+            return Err(SessionError::MissingSnapshot);
+        }
+
+        Ok(session)
+    }
+
+    // The number of recorded messages.
+    fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    // Rebuilds the state up to the entry at `end` from the closest snapshot
+    // before it.
+    fn replay_until(&self, end: usize) -> State {
+        // The first entry is always a snapshot.
+        let start = self.snapshots[self.snapshots.partition_point(|&i| i <= end) - 1];
+        let SessionEntry::Snapshot(snapshot) = &self.entries[start] else {
+            unreachable!("`snapshots` only points at snapshots");
+        };
+
+        let mut state = State::from_snapshot(snapshot);
+        for entry in &self.entries[start + 1..=end] {
+            if let SessionEntry::Message(message) = entry {
+                state.process(message.clone());
+            }
+        }
+        state
+    }
+
+    // The state after the first `n` messages, or `None` if there are fewer.
+    fn seek(&self, n: usize) -> Option<State> {
+        let end = match n {
+            0 => 0,
+            n => *self.messages.get(n - 1)?,
+        };
+        Some(self.replay_until(end))
+    }
+
+    // The state at the end of the session.
+    fn replay(&self) -> State {
+        self.replay_until(self.entries.len() - 1)
+    }
+}
+
+#[derive(Debug)]
+enum StateRequest {
+    Process(Message),
+    // Answered with a snapshot of the state at the time it is handled.
+    Query(Sender<Snapshot>),
+}
+
+// Asks a state actor for a snapshot. Returns `None` if the actor stopped.
+fn query(sender: &Sender<StateRequest>) -> Option<Snapshot> {
+    let (reply, receiver) = mpsc::channel();
+    sender.send(StateRequest::Query(reply)).ok()?;
+    receiver.recv().ok()
+}
+
+impl State {
+    // Moves the state into its own thread, which handles requests in the
+    // order they arrive. It stops after processing `Message::Quit` or once
+    // every sender is dropped, and returns the final state.
+    fn spawn(mut self) -> (Sender<StateRequest>, JoinHandle<State>) {
+        let (sender, receiver) = mpsc::channel();
+
+        let handle = thread::spawn(move || {
+            for request in receiver {
+                match request {
+                    StateRequest::Process(message) => {
+                        let quit = message == Message::Quit;
+                        self.process(message);
+                        if quit {
+                            break;
+                        }
+                    }
+                    StateRequest::Query(reply) => {
+                        // The caller may have stopped waiting for the answer.
+                        let _ = reply.send(self.snapshot());
+                    }
+                }
+            }
+            self
+        });
+
+        (sender, handle)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum RenderMode {
+    Plain,
+    // The cursor is drawn in the state's color with 24-bit ANSI escapes.
+    Ansi,
+}
+
+// Draws the canvas as one line of characters per row, followed by a status
+// line with the echoed message. Only the top left `max_width` x `max_height`
+// cells are drawn, so a huge canvas doesn't flood the terminal.
+#[derive(Debug)]
+struct Renderer {
+    mode: RenderMode,
+    empty: char,
+    cursor: char,
+    max_width: u64,
+    max_height: u64,
+}
+
+impl Renderer {
+    fn new(mode: RenderMode) -> Self {
+        Self {
+            mode,
+            empty: '.',
+            cursor: '@',
+            max_width: 80,
+            max_height: 24,
+        }
+    }
+
+    fn render(&self, state: &State) -> String {
+        let mut output = String::new();
+
+        for y in 0..state.height.min(self.max_height) {
+            for x in 0..state.width.min(self.max_width) {
+                if (Point { x, y }) != state.position {
+                    output.push(self.empty);
+                    continue;
+                }
+                match self.mode {
+                    RenderMode::Plain => output.push(self.cursor),
+                    RenderMode::Ansi => {
+                        let (r, g, b) = state.color;
+                        output.push_str(&format!("\x1b[38;2;{r};{g};{b}m{}\x1b[0m", self.cursor));
+                    }
+                }
+            }
+            output.push('\n');
+        }
+
+        // Control characters would break the layout.
+        output.push_str("> ");
+        for c in state.message.chars() {
+            if c.is_control() {
+                output.extend(c.escape_default());
+            } else {
+                output.push(c);
+            }
+        }
+        output.push('\n');
+
+        output
+    }
 }
 
 fn main() {
@@ -70,6 +1194,7 @@ mod tests {
             message: String::from("hello world"),
             color: (0, 0, 0),
             quit: false,
+            ..Default::default()
         };
 
         state.process(Message::Resize {
@@ -89,4 +1214,804 @@ mod tests {
         assert_eq!(state.color, (255, 0, 255));
         assert!(state.quit);
     }
+
+    fn messages() -> Vec<Message> {
+        vec![
+            Message::Resize {
+                width: 10,
+                height: 30,
+            },
+            Message::Move(Point { x: 10, y: 15 }),
+            Message::Echo(String::from("Hello world!")),
+            Message::ChangeColor(255, 0, 255),
+            Message::Quit,
+        ]
+    }
+
+    #[test]
+    fn undo_everything() {
+        let mut state = State::default();
+        for message in messages() {
+            state.process(message);
+        }
+
+        assert!(state.undo());
+        assert!(!state.quit);
+        while state.undo() {}
+
+        assert_eq!(state.width, 0);
+        assert_eq!(state.height, 0);
+        assert_eq!(state.position, Point { x: 0, y: 0 });
+        assert_eq!(state.message, "");
+        assert_eq!(state.color, (0, 0, 0));
+        assert_eq!(state.history().count(), 0);
+    }
+
+    #[test]
+    fn redo_after_undo() {
+        let mut state = State::default();
+        for message in messages() {
+            state.process(message);
+        }
+
+        state.undo();
+        state.undo();
+        assert_eq!(state.color, (0, 0, 0));
+        assert!(state.redo());
+        assert!(state.redo());
+        assert!(!state.redo());
+
+        assert_eq!(state.color, (255, 0, 255));
+        assert!(state.quit);
+        assert_eq!(state.history().cloned().collect::<Vec<_>>(), messages());
+    }
+
+    #[test]
+    fn new_message_drops_redo_branch() {
+        let mut state = State::default();
+        state.process(Message::Echo(String::from("first")));
+        state.process(Message::Echo(String::from("second")));
+
+        state.undo();
+        state.process(Message::Echo(String::from("third")));
+
+        assert!(!state.redo());
+        assert_eq!(state.message, "third");
+        state.undo();
+        assert_eq!(state.message, "first");
+    }
+
+    #[test]
+    fn history_depth_limits_undo() {
+        let mut state = State::default();
+        state.set_history_depth(2);
+        for message in messages() {
+            state.process(message);
+        }
+
+        assert_eq!(
+            state.history().collect::<Vec<_>>(),
+            [&Message::ChangeColor(255, 0, 255), &Message::Quit],
+        );
+        assert!(state.undo());
+        assert!(state.undo());
+        assert!(!state.undo());
+        assert_eq!(state.message, "Hello world!");
+
+        state.set_history_depth(0);
+        state.process(Message::Quit);
+        assert!(!state.undo());
+    }
+
+    #[test]
+    fn parse_messages() {
+        assert_eq!(
+            "resize 10 30".parse(),
+            Ok(Message::Resize {
+                width: 10,
+                height: 30,
+            }),
+        );
+        assert_eq!(
+            "  move 10   15 ".parse(),
+            Ok(Message::Move(Point { x: 10, y: 15 })),
+        );
+        assert_eq!(
+            r#"echo "hello world""#.parse(),
+            Ok(Message::Echo(String::from("hello world"))),
+        );
+        assert_eq!(
+            "echo hello".parse(),
+            Ok(Message::Echo(String::from("hello"))),
+        );
+        assert_eq!(
+            r#"echo "say \"hi\"\n""#.parse(),
+            Ok(Message::Echo(String::from("say \"hi\"\n"))),
+        );
+        assert_eq!(
+            "color #FF00ff".parse(),
+            Ok(Message::ChangeColor(255, 0, 255))
+        );
+        assert_eq!("quit".parse(), Ok(Message::Quit));
+    }
+
+    fn parse_error(s: &str) -> (usize, ParseMessageErrorKind) {
+        let err = s.parse::<Message>().unwrap_err();
+        (err.column, err.kind)
+    }
+
+    #[test]
+    fn fail_parsing_with_columns() {
+        assert_eq!(parse_error("  "), (3, ParseMessageErrorKind::Empty));
+        assert_eq!(
+            parse_error("jump 1 2"),
+            (
+                1,
+                ParseMessageErrorKind::UnknownCommand(String::from("jump"))
+            ),
+        );
+        assert_eq!(
+            parse_error("resize 10"),
+            (10, ParseMessageErrorKind::MissingArgument),
+        );
+        assert_eq!(
+            parse_error("move 10 -15"),
+            (9, ParseMessageErrorKind::InvalidNumber(String::from("-15"))),
+        );
+        assert_eq!(
+            parse_error("quit now"),
+            (6, ParseMessageErrorKind::UnexpectedArgument),
+        );
+        assert_eq!(
+            parse_error("color #ff00"),
+            (
+                7,
+                ParseMessageErrorKind::InvalidColor(String::from("#ff00"))
+            ),
+        );
+        assert_eq!(
+            parse_error(r#"echo "hello"#),
+            (6, ParseMessageErrorKind::UnterminatedString),
+        );
+        assert_eq!(
+            parse_error(r#"echo "a\qb""#),
+            (8, ParseMessageErrorKind::InvalidEscape('q')),
+        );
+        assert_eq!(
+            "échø \"x\" y".parse::<Message>().unwrap_err().to_string(),
+            "column 1: unknown command \"échø\"",
+        );
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let mut messages = messages();
+        messages.push(Message::Echo(String::from(
+            "tab\there \"quoted\" back\\slash\r\n",
+        )));
+        messages.push(Message::Echo(String::new()));
+
+        for message in messages {
+            let line = message.to_string();
+            assert_eq!(line.parse(), Ok(message), "{line}");
+        }
+        assert_eq!(
+            Message::ChangeColor(255, 0, 15).to_string(),
+            "color #ff000f"
+        );
+        assert_eq!(
+            Message::Echo(String::from("hello world")).to_string(),
+            r#"echo "hello world""#,
+        );
+    }
+
+    fn encode(message: &Message) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        message.encode(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn encode_messages() {
+        assert_eq!(
+            encode(&Message::Resize {
+                width: 10,
+                height: 300,
+            }),
+            [0, 10, 0xac, 0x02],
+        );
+        assert_eq!(encode(&Message::Move(Point { x: 0, y: u64::MAX })), {
+            let mut bytes = vec![1, 0];
+            bytes.extend([0xff; 9]);
+            bytes.push(0x01);
+            bytes
+        });
+        assert_eq!(
+            encode(&Message::Echo(String::from("hé"))),
+            [2, 3, b'h', 0xc3, 0xa9]
+        );
+        assert_eq!(encode(&Message::ChangeColor(255, 0, 15)), [3, 255, 0, 15]);
+        assert_eq!(encode(&Message::Quit), [4]);
+    }
+
+    #[test]
+    fn decode_message_stream() {
+        let mut messages = messages();
+        messages.push(Message::Move(Point {
+            x: u64::MAX,
+            y: 1 << 35,
+        }));
+        let mut bytes = Vec::new();
+        for message in &messages {
+            message.encode(&mut bytes).unwrap();
+        }
+
+        let mut reader = bytes.as_slice();
+        for message in messages {
+            assert_eq!(Message::decode(&mut reader).unwrap(), message);
+        }
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn fail_decoding_truncated_messages() {
+        let bytes = encode(&Message::Echo(String::from("hello")));
+        for len in 0..bytes.len() {
+            assert!(
+                matches!(
+                    Message::decode(&mut &bytes[..len]),
+                    Err(DecodeError::Truncated),
+                ),
+                "{len}",
+            );
+        }
+        assert!(matches!(
+            Message::decode(&mut [0, 0x80].as_slice()),
+            Err(DecodeError::Truncated),
+        ));
+        assert!(matches!(
+            Message::decode(&mut [3, 1, 2].as_slice()),
+            Err(DecodeError::Truncated),
+        ));
+    }
+
+    #[test]
+    fn fail_decoding_invalid_messages() {
+        assert!(matches!(
+            Message::decode(&mut [9].as_slice()),
+            Err(DecodeError::UnknownTag(9)),
+        ));
+        assert!(matches!(
+            Message::decode(&mut [2, 2, 0xc3, 0x28].as_slice()),
+            Err(DecodeError::InvalidUtf8(_)),
+        ));
+        let mut too_large = vec![0];
+        too_large.extend([0xff; 9]);
+        too_large.push(0x02);
+        assert!(matches!(
+            Message::decode(&mut too_large.as_slice()),
+            Err(DecodeError::VarintOverflow),
+        ));
+    }
+
+    #[test]
+    fn listeners_see_changes() {
+        use std::sync::{Arc, Mutex};
+
+        let mut state = State::default();
+        let redraws = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        state.subscribe({
+            let redraws = Arc::clone(&redraws);
+            move |change| {
+                if let Change::Size { .. } = change.after {
+                    redraws.lock().unwrap().push(change.after.clone());
+                }
+            }
+        });
+        state.subscribe({
+            let log = Arc::clone(&log);
+            move |change| {
+                if let Change::Message(s) = &change.after {
+                    log.lock().unwrap().push(s.clone());
+                }
+            }
+        });
+
+        for message in messages() {
+            state.process(message);
+        }
+        state.undo();
+        state.undo();
+        state.undo();
+
+        assert_eq!(
+            *redraws.lock().unwrap(),
+            [Change::Size {
+                width: 10,
+                height: 30,
+            }],
+        );
+        // Undoing the echo restores the empty message.
+        assert_eq!(*log.lock().unwrap(), ["Hello world!", ""]);
+    }
+
+    #[test]
+    fn listeners_get_before_and_after() {
+        use std::sync::mpsc;
+
+        let mut state = State::default();
+        let (sender, receiver) = mpsc::channel();
+        state.subscribe(move |change: &StateChange| {
+            sender
+                .send((change.before.clone(), change.after.clone()))
+                .unwrap()
+        });
+
+        state.process(Message::ChangeColor(1, 2, 3));
+        state.process(Message::ChangeColor(4, 5, 6));
+
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            [
+                (Change::Color(0, 0, 0), Change::Color(1, 2, 3)),
+                (Change::Color(1, 2, 3), Change::Color(4, 5, 6)),
+            ],
+        );
+    }
+
+    #[test]
+    fn unsubscribe_listener() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let mut state = State::default();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let handle = state.subscribe({
+            let calls = Arc::clone(&calls);
+            move |_| {
+                calls.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        state.process(Message::Quit);
+        assert!(state.unsubscribe(handle));
+        assert!(!state.unsubscribe(handle));
+        state.process(Message::Quit);
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn listeners_run_after_the_whole_message_is_applied() {
+        use std::sync::{Arc, Mutex};
+
+        let mut state = bounded_state(BoundsPolicy::Clamp);
+        state.process(Message::Move(Point { x: 4, y: 5 }));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        state.subscribe({
+            let seen = Arc::clone(&seen);
+            move |change| seen.lock().unwrap().push(change.after.clone())
+        });
+
+        // Shrinking the canvas also clamps the position.
+        state.process(Message::Resize {
+            width: 2,
+            height: 2,
+        });
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                Change::Size {
+                    width: 2,
+                    height: 2,
+                },
+                Change::Position(Point { x: 1, y: 1 }),
+            ],
+        );
+        assert_eq!((state.width, state.height), (2, 2));
+        assert_eq!(state.position, Point { x: 1, y: 1 });
+    }
+
+    fn bounded_state(policy: BoundsPolicy) -> State {
+        let mut state = State::default();
+        state.process(Message::Resize {
+            width: 10,
+            height: 30,
+        });
+        state.set_bounds_policy(Some(policy));
+        state
+    }
+
+    #[test]
+    fn clamp_positions_outside_canvas() {
+        let mut state = bounded_state(BoundsPolicy::Clamp);
+
+        state
+            .try_process(Message::Move(Point { x: 4, y: 5 }))
+            .unwrap();
+        assert_eq!(state.position, Point { x: 4, y: 5 });
+        state
+            .try_process(Message::Move(Point { x: 10, y: 50 }))
+            .unwrap();
+        assert_eq!(state.position, Point { x: 9, y: 29 });
+    }
+
+    #[test]
+    fn reject_positions_outside_canvas() {
+        let mut state = bounded_state(BoundsPolicy::Reject);
+        state.process(Message::Move(Point { x: 4, y: 5 }));
+
+        assert_eq!(
+            state.try_process(Message::Move(Point { x: 10, y: 15 })),
+            Err(BoundsError::OutOfBounds {
+                point: Point { x: 10, y: 15 },
+                width: 10,
+                height: 30,
+            }),
+        );
+        state.process(Message::Move(Point { x: 3, y: 30 }));
+
+        assert_eq!(state.position, Point { x: 4, y: 5 });
+        assert_eq!(state.history().count(), 2);
+    }
+
+    #[test]
+    fn wrap_positions_outside_canvas() {
+        let mut state = bounded_state(BoundsPolicy::Wrap);
+
+        state
+            .try_process(Message::Move(Point { x: 23, y: 30 }))
+            .unwrap();
+
+        assert_eq!(state.position, Point { x: 3, y: 0 });
+    }
+
+    #[test]
+    fn shrinking_resize_reapplies_policy() {
+        let mut state = bounded_state(BoundsPolicy::Clamp);
+        state.process(Message::Move(Point { x: 8, y: 20 }));
+        state.process(Message::Resize {
+            width: 5,
+            height: 25,
+        });
+        assert_eq!(state.position, Point { x: 4, y: 20 });
+
+        // Undoing the resize also restores the position.
+        state.undo();
+        assert_eq!((state.width, state.height), (10, 30));
+        assert_eq!(state.position, Point { x: 8, y: 20 });
+        state.redo();
+        assert_eq!(state.position, Point { x: 4, y: 20 });
+
+        let mut state = bounded_state(BoundsPolicy::Wrap);
+        state.process(Message::Move(Point { x: 8, y: 20 }));
+        state.process(Message::Resize {
+            width: 5,
+            height: 25,
+        });
+        assert_eq!(state.position, Point { x: 3, y: 20 });
+
+        let mut state = bounded_state(BoundsPolicy::Reject);
+        state.process(Message::Move(Point { x: 8, y: 20 }));
+        assert!(matches!(
+            state.try_process(Message::Resize {
+                width: 5,
+                height: 25,
+            }),
+            Err(BoundsError::OutOfBounds { .. }),
+        ));
+        assert_eq!((state.width, state.height), (10, 30));
+    }
+
+    #[test]
+    fn fail_using_empty_canvas() {
+        let mut state = State::default();
+        state.set_bounds_policy(Some(BoundsPolicy::Clamp));
+
+        assert_eq!(
+            state.try_process(Message::Move(Point { x: 0, y: 0 })),
+            Err(BoundsError::EmptyCanvas),
+        );
+        assert_eq!(
+            state.try_process(Message::Resize {
+                width: 0,
+                height: 10,
+            }),
+            Err(BoundsError::EmptyCanvas),
+        );
+    }
+
+    fn session_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("enums3_{name}_{}.log", std::process::id()))
+    }
+
+    fn record(name: &str, snapshot_every: usize, run: impl FnOnce(&mut State)) -> String {
+        let path = session_path(name);
+        let mut state = State::default();
+        state.start_recording(std::fs::File::create(&path).unwrap(), snapshot_every);
+        run(&mut state);
+        state.stop_recording().unwrap();
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        log
+    }
+
+    #[test]
+    fn record_session_log() {
+        let log = record("log", 2, |state| {
+            for message in messages() {
+                state.process(message);
+            }
+        });
+
+        assert_eq!(
+            log,
+            r#"snapshot 0 0 0 0 #000000 false none ""
+resize 10 30
+move 10 15
+snapshot 10 30 10 15 #000000 false none ""
+echo "Hello world!"
+color #ff00ff
+snapshot 10 30 10 15 #ff00ff false none "Hello world!"
+quit
+"#,
+        );
+    }
+
+    #[test]
+    fn replay_session() {
+        let log = record("replay", 3, |state| {
+            state.set_bounds_policy(Some(BoundsPolicy::Clamp));
+            state.process(Message::Resize {
+                width: 10,
+                height: 30,
+            });
+            state.process(Message::Move(Point { x: 50, y: 15 }));
+            state.process(Message::Echo(String::from("multi\nline \"echo\"")));
+            state.undo();
+            state.process(Message::ChangeColor(1, 2, 3));
+            state.process(Message::Quit);
+        });
+
+        let session = Session::load(log.as_bytes()).unwrap();
+        assert_eq!(session.len(), 5);
+
+        let state = session.replay();
+        assert_eq!(state.position, Point { x: 9, y: 15 });
+        assert_eq!(state.message, "");
+        assert_eq!(state.color, (1, 2, 3));
+        assert!(state.quit);
+
+        let state = session.seek(0).unwrap();
+        assert_eq!((state.width, state.height), (0, 0));
+        let state = session.seek(3).unwrap();
+        assert_eq!(state.message, "multi\nline \"echo\"");
+        assert_eq!(state.bounds_policy, Some(BoundsPolicy::Clamp));
+        let state = session.seek(4).unwrap();
+        assert_eq!(state.message, "");
+        assert_eq!(state.color, (1, 2, 3));
+        assert!(!state.quit);
+        assert!(session.seek(6).is_none());
+    }
+
+    #[test]
+    fn seek_matches_live_state() {
+        let mut live = Vec::new();
+        let log = record("seek", 2, |state| {
+            live.push(state.snapshot());
+            for i in 0..7 {
+                state.process(Message::Move(Point { x: i, y: i * 2 }));
+                live.push(state.snapshot());
+                state.process(Message::Echo(format!("step {i}")));
+                live.push(state.snapshot());
+            }
+        });
+
+        let session = Session::load(log.as_bytes()).unwrap();
+        for (n, snapshot) in live.iter().enumerate() {
+            assert_eq!(session.seek(n).unwrap().snapshot(), *snapshot, "{n}");
+        }
+    }
+
+    #[test]
+    fn parse_bounds_policy() {
+        for policy in [
+            BoundsPolicy::Clamp,
+            BoundsPolicy::Reject,
+            BoundsPolicy::Wrap,
+        ] {
+            assert_eq!(policy.name().parse(), Ok(policy));
+        }
+        assert_eq!(
+            "none".parse::<BoundsPolicy>(),
+            Err(ParseBoundsPolicyError(String::from("none"))),
+        );
+    }
+
+    #[test]
+    fn snapshot_round_trips() {
+        let snapshot = Snapshot {
+            width: 10,
+            height: 30,
+            position: Point { x: 4, y: 5 },
+            message: String::from("say \"hi\""),
+            color: (255, 0, 255),
+            quit: true,
+            bounds_policy: Some(BoundsPolicy::Wrap),
+        };
+
+        assert_eq!(snapshot.to_string().parse(), Ok(snapshot.clone()));
+        let snapshot = Snapshot {
+            bounds_policy: None,
+            ..snapshot
+        };
+        assert_eq!(snapshot.to_string().parse(), Ok(snapshot));
+        assert_eq!(
+            "snapshot 1 2 3 4 #000000 false clip \"\"".parse::<Snapshot>(),
+            Err(ParseMessageError {
+                column: 32,
+                kind: ParseMessageErrorKind::InvalidValue(String::from("clip")),
+            }),
+        );
+        assert_eq!(
+            "snapshot 1 2 3 4 #000000 maybe none \"\"".parse::<Snapshot>(),
+            Err(ParseMessageError {
+                column: 26,
+                kind: ParseMessageErrorKind::InvalidValue(String::from("maybe")),
+            }),
+        );
+    }
+
+    #[test]
+    fn fail_loading_invalid_sessions() {
+        assert!(matches!(
+            Session::load("quit\n".as_bytes()),
+            Err(SessionError::MissingSnapshot),
+        ));
+        assert!(matches!(
+            Session::load("".as_bytes()),
+            Err(SessionError::MissingSnapshot),
+        ));
+        match Session::load("snapshot 0 0 0 0 #000000 false none \"\"\nquit\njump\n".as_bytes()) {
+            Err(SessionError::Parse { line, error }) => {
+                assert_eq!(line, 3);
+                assert_eq!(
+                    error.kind,
+                    ParseMessageErrorKind::UnknownCommand(String::from("jump")),
+                );
+            }
+            other => panic!("expected a parse error, got {other:?}"),
+        }
+    }
+
+    fn rendered_state() -> State {
+        let mut state = State::default();
+        state.process(Message::Resize {
+            width: 6,
+            height: 3,
+        });
+        state.process(Message::Move(Point { x: 2, y: 1 }));
+        state.process(Message::Echo(String::from("Hello world!")));
+        state.process(Message::ChangeColor(255, 0, 128));
+        state
+    }
+
+    #[test]
+    fn render_plain() {
+        assert_eq!(
+            Renderer::new(RenderMode::Plain).render(&rendered_state()),
+            "\
+......
+..@...
+......
+> Hello world!
+",
+        );
+    }
+
+    #[test]
+    fn render_ansi() {
+        assert_eq!(
+            Renderer::new(RenderMode::Ansi).render(&rendered_state()),
+            "\
+......
+..\x1b[38;2;255;0;128m@\x1b[0m...
+......
+> Hello world!
+",
+        );
+    }
+
+    #[test]
+    fn render_cursor_outside_canvas() {
+        let mut state = rendered_state();
+        state.process(Message::Move(Point { x: 6, y: 0 }));
+        state.process(Message::Echo(String::from("tab\there\nnext")));
+
+        assert_eq!(
+            Renderer::new(RenderMode::Plain).render(&state),
+            "\
+......
+......
+......
+> tab\\there\\nnext
+",
+        );
+    }
+
+    #[test]
+    fn render_crops_large_canvas() {
+        let mut state = State::default();
+        state.process(Message::Resize {
+            width: u64::MAX,
+            height: u64::MAX,
+        });
+        state.process(Message::Move(Point { x: 3, y: 1 }));
+        let renderer = Renderer {
+            empty: ' ',
+            cursor: '#',
+            max_width: 4,
+            max_height: 2,
+            ..Renderer::new(RenderMode::Plain)
+        };
+
+        assert_eq!(renderer.render(&state), "    \n   #\n> \n");
+    }
+
+    #[test]
+    fn actor_processes_messages_until_quit() {
+        let (sender, handle) = State::default().spawn();
+
+        let workers: Vec<_> = (0..4)
+            .map(|i| {
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    sender
+                        .send(StateRequest::Process(Message::Echo(format!("worker {i}"))))
+                        .unwrap();
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        sender
+            .send(StateRequest::Process(Message::Resize {
+                width: 10,
+                height: 30,
+            }))
+            .unwrap();
+
+        let snapshot = query(&sender).unwrap();
+        assert_eq!((snapshot.width, snapshot.height), (10, 30));
+        assert!(snapshot.message.starts_with("worker "));
+        assert!(!snapshot.quit);
+
+        sender.send(StateRequest::Process(Message::Quit)).unwrap();
+        let state = handle.join().unwrap();
+
+        assert!(state.quit);
+        assert_eq!(state.history().count(), 6);
+        assert!(query(&sender).is_none());
+        assert!(sender.send(StateRequest::Process(Message::Quit)).is_err());
+    }
+
+    #[test]
+    fn actor_stops_when_senders_are_dropped() {
+        let (sender, handle) = State::default().spawn();
+        let other = sender.clone();
+
+        sender
+            .send(StateRequest::Process(Message::ChangeColor(1, 2, 3)))
+            .unwrap();
+        drop(sender);
+        drop(other);
+
+        let state = handle.join().unwrap();
+        assert_eq!(state.color, (1, 2, 3));
+        assert!(!state.quit);
+    }
 }
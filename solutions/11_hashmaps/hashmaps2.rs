@@ -9,8 +9,12 @@
 // Mango, and Lychee).
 
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::str::FromStr;
 
-#[derive(Hash, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Debug)]
 enum Fruit {
     Apple,
     Banana,
@@ -19,8 +23,8 @@ enum Fruit {
     Pineapple,
 }
 
-fn fruit_basket(basket: &mut HashMap<Fruit, u32>) {
-    let fruit_kinds = [
+impl Fruit {
+    const ALL: [Fruit; 5] = [
         Fruit::Apple,
         Fruit::Banana,
         Fruit::Mango,
@@ -28,9 +32,363 @@ fn fruit_basket(basket: &mut HashMap<Fruit, u32>) {
         Fruit::Pineapple,
     ];
 
-    for fruit in fruit_kinds {
-        // If fruit doesn't exist, insert it with some value.
-        basket.entry(fruit).or_insert(5);
+    fn name(self) -> &'static str {
+        match self {
+            Fruit::Apple => "apple",
+            Fruit::Banana => "banana",
+            Fruit::Mango => "mango",
+            Fruit::Lychee => "lychee",
+            Fruit::Pineapple => "pineapple",
+        }
+    }
+}
+
+impl fmt::Display for Fruit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(PartialEq, Debug)]
+struct ParseFruitError {
+    input: String,
+    // The closest known fruit, if any is close enough to be a typo.
+    suggestion: Option<Fruit>,
+}
+
+impl fmt::Display for ParseFruitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown fruit {:?}", self.input)?;
+        match self.suggestion {
+            Some(fruit) => write!(f, ", did you mean {:?}?", fruit.name()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Error for ParseFruitError {}
+
+// The number of single character insertions, deletions or substitutions that
+// turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+// Accepts names in any case and their plurals, like "Apple", "apples" or
+// "MANGOES".
+impl FromStr for Fruit {
+    type Err = ParseFruitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let input = s.trim().to_lowercase();
+        let candidates = [
+            Some(input.as_str()),
+            input.strip_suffix('s'),
+            input.strip_suffix("es"),
+        ];
+
+        for candidate in candidates.into_iter().flatten() {
+            if let Some(fruit) = Fruit::ALL.into_iter().find(|f| f.name() == candidate) {
+                return Ok(fruit);
+            }
+        }
+
+        let suggestion = Fruit::ALL
+            .into_iter()
+            .map(|fruit| (edit_distance(&input, fruit.name()), fruit))
+            .min()
+            .filter(|(distance, fruit)| *distance <= fruit.name().len() / 2)
+            .map(|(_, fruit)| fruit);
+
+        Err(ParseFruitError {
+            input: s.to_string(),
+            suggestion,
+        })
+    }
+}
+
+// Items below `min_stock` get `restock_quantity` more, or as many as they need
+// to reach `min_stock` if that's more, but never more than `max_capacity` in
+// total.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct RestockPolicy {
+    min_stock: u32,
+    max_capacity: u32,
+    restock_quantity: u32,
+}
+
+impl Default for RestockPolicy {
+    // Only restock items that ran out, 5 at a time.
+    fn default() -> Self {
+        Self {
+            min_stock: 1,
+            max_capacity: u32::MAX,
+            restock_quantity: 5,
+        }
+    }
+}
+
+impl RestockPolicy {
+    fn restock_amount(&self, stock: u32) -> u32 {
+        if stock >= self.min_stock {
+            return 0;
+        }
+
+        self.restock_quantity
+            .max(self.min_stock - stock)
+            .min(self.max_capacity.saturating_sub(stock))
+    }
+}
+
+#[derive(PartialEq, Debug)]
+enum InventoryError {
+    Shortage { requested: u32, available: u32 },
+}
+
+impl fmt::Display for InventoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InventoryError::Shortage {
+                requested,
+                available,
+            } => write!(f, "requested {requested} but only {available} available"),
+        }
+    }
+}
+
+impl Error for InventoryError {}
+
+#[derive(Debug)]
+struct Inventory<K> {
+    stock: HashMap<K, u32>,
+    policies: HashMap<K, RestockPolicy>,
+    default_policy: RestockPolicy,
+}
+
+impl<K: Hash + Eq + Clone> Inventory<K> {
+    fn new(stock: HashMap<K, u32>) -> Self {
+        Self {
+            stock,
+            policies: HashMap::new(),
+            default_policy: RestockPolicy::default(),
+        }
+    }
+
+    fn set_policy(&mut self, item: K, policy: RestockPolicy) {
+        self.policies.insert(item, policy);
+    }
+
+    fn policy(&self, item: &K) -> &RestockPolicy {
+        self.policies.get(item).unwrap_or(&self.default_policy)
+    }
+
+    // Starts keeping stock of `item`, if it isn't already.
+    fn track(&mut self, item: K) {
+        self.stock.entry(item).or_insert(0);
+    }
+
+    fn stock(&self, item: &K) -> u32 {
+        self.stock.get(item).copied().unwrap_or(0)
+    }
+
+    // Returns how many of each item were added. Items that didn't need
+    // restocking are left out.
+    fn restock(&mut self) -> HashMap<K, u32> {
+        let mut added = HashMap::new();
+
+        for (item, stock) in &self.stock {
+            let amount = self.policy(item).restock_amount(*stock);
+            if amount > 0 {
+                added.insert(item.clone(), amount);
+            }
+        }
+        for (item, amount) in &added {
+            // `restock_amount` stays below `max_capacity - stock`.
+            *self.stock.get_mut(item).unwrap() += amount;
+        }
+
+        added
+    }
+
+    fn consume(&mut self, item: &K, n: u32) -> Result<(), InventoryError> {
+        let available = self.stock(item);
+        if available < n {
+            return Err(InventoryError::Shortage {
+                requested: n,
+                available,
+            });
+        }
+
+        if let Some(stock) = self.stock.get_mut(item) {
+            *stock -= n;
+        }
+        Ok(())
+    }
+
+    fn into_stock(self) -> HashMap<K, u32> {
+        self.stock
+    }
+}
+
+fn fruit_basket(basket: &mut HashMap<Fruit, u32>) {
+    // Only fruit that isn't in the basket at all is restocked. Fruit that is
+    // already there must be left alone, even with a count of 0.
+    let mut inventory = Inventory::new(HashMap::new());
+    for fruit in Fruit::ALL {
+        if !basket.contains_key(&fruit) {
+            inventory.track(fruit);
+        }
+    }
+    inventory.restock();
+    basket.extend(inventory.into_stock());
+}
+
+// A basket of fruit as counted in `fruit_basket`. The operations never keep
+// fruit with a count of 0 in their results.
+#[derive(Clone, PartialEq, Default, Debug)]
+struct Basket(HashMap<Fruit, u32>);
+
+#[derive(PartialEq, Debug)]
+enum BasketError {
+    Overflow(Fruit),
+    // What was missing to take the whole other basket out.
+    Shortfall(Basket),
+}
+
+impl fmt::Display for BasketError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BasketError::Overflow(fruit) => write!(f, "too many of {fruit} for one basket"),
+            BasketError::Shortfall(shortfall) => write!(f, "missing {shortfall}"),
+        }
+    }
+}
+
+impl Error for BasketError {}
+
+// What's left after taking one basket out of another, and what couldn't be
+// taken because it was missing.
+#[derive(PartialEq, Debug)]
+struct Difference {
+    remaining: Basket,
+    shortfall: Basket,
+}
+
+impl From<HashMap<Fruit, u32>> for Basket {
+    fn from(map: HashMap<Fruit, u32>) -> Self {
+        Self(map)
+    }
+}
+
+impl From<Basket> for HashMap<Fruit, u32> {
+    fn from(basket: Basket) -> Self {
+        basket.0
+    }
+}
+
+impl Basket {
+    fn count(&self, fruit: Fruit) -> u32 {
+        self.0.get(&fruit).copied().unwrap_or(0)
+    }
+
+    // Builds a basket from the counts of every fruit, without the zeros.
+    fn from_counts(counts: impl Fn(Fruit) -> u32) -> Self {
+        Self(
+            Fruit::ALL
+                .into_iter()
+                .map(|fruit| (fruit, counts(fruit)))
+                .filter(|(_, count)| *count > 0)
+                .collect(),
+        )
+    }
+
+    fn merge(&self, other: &Basket) -> Result<Basket, BasketError> {
+        let mut merged = HashMap::new();
+        for fruit in Fruit::ALL {
+            let count = self
+                .count(fruit)
+                .checked_add(other.count(fruit))
+                .ok_or(BasketError::Overflow(fruit))?;
+            if count > 0 {
+                merged.insert(fruit, count);
+            }
+        }
+
+        Ok(Self(merged))
+    }
+
+    fn difference(&self, other: &Basket) -> Difference {
+        Difference {
+            remaining: Self::from_counts(|fruit| {
+                self.count(fruit).saturating_sub(other.count(fruit))
+            }),
+            shortfall: Self::from_counts(|fruit| {
+                other.count(fruit).saturating_sub(self.count(fruit))
+            }),
+        }
+    }
+
+    // Like `difference`, but fails instead of taking out only part of `other`.
+    fn checked_sub(&self, other: &Basket) -> Result<Basket, BasketError> {
+        let Difference {
+            remaining,
+            shortfall,
+        } = self.difference(other);
+
+        if shortfall.0.is_empty() {
+            Ok(remaining)
+        } else {
+            Err(BasketError::Shortfall(shortfall))
+        }
+    }
+
+    fn intersection(&self, other: &Basket) -> Basket {
+        Self::from_counts(|fruit| self.count(fruit).min(other.count(fruit)))
+    }
+
+    fn scaled(&self, factor: u32) -> Result<Basket, BasketError> {
+        let mut scaled = HashMap::new();
+        for (fruit, count) in &self.0 {
+            let count = count
+                .checked_mul(factor)
+                .ok_or(BasketError::Overflow(*fruit))?;
+            if count > 0 {
+                scaled.insert(*fruit, count);
+            }
+        }
+
+        Ok(Self(scaled))
+    }
+}
+
+// Sorted by fruit, for example "apple: 4, mango: 2, lychee: 5".
+impl fmt::Display for Basket {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut fruits: Vec<_> = self.0.iter().collect();
+        fruits.sort();
+
+        if fruits.is_empty() {
+            return f.write_str("nothing");
+        }
+        for (i, (fruit, count)) in fruits.into_iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{fruit}: {count}")?;
+        }
+        Ok(())
     }
 }
 
@@ -75,22 +433,231 @@ mod tests {
 
     #[test]
     fn all_fruit_types_in_basket() {
-        let fruit_kinds = [
-            Fruit::Apple,
-            Fruit::Banana,
-            Fruit::Mango,
-            Fruit::Lychee,
-            Fruit::Pineapple,
-        ];
-
         let mut basket = get_fruit_basket();
         fruit_basket(&mut basket);
 
-        for fruit_kind in fruit_kinds {
+        for fruit_kind in Fruit::ALL {
             let Some(amount) = basket.get(&fruit_kind) else {
                 panic!("Fruit kind {fruit_kind:?} was not found in basket");
             };
             assert!(*amount > 0);
         }
     }
+
+    #[test]
+    fn existing_fruit_with_zero_count_is_not_modified() {
+        let mut basket = HashMap::from([(Fruit::Apple, 0), (Fruit::Mango, 2)]);
+        fruit_basket(&mut basket);
+        assert_eq!(basket.get(&Fruit::Apple), Some(&0));
+        assert_eq!(basket.get(&Fruit::Mango), Some(&2));
+        assert_eq!(basket.get(&Fruit::Banana), Some(&5));
+    }
+
+    #[test]
+    fn restock_reports_added_fruit() {
+        let mut inventory = Inventory::new(get_fruit_basket());
+        inventory.track(Fruit::Banana);
+        inventory.set_policy(
+            Fruit::Mango,
+            RestockPolicy {
+                min_stock: 3,
+                max_capacity: 10,
+                restock_quantity: 6,
+            },
+        );
+        inventory.set_policy(
+            Fruit::Apple,
+            RestockPolicy {
+                min_stock: 20,
+                max_capacity: 12,
+                restock_quantity: 1,
+            },
+        );
+
+        let added = inventory.restock();
+
+        assert_eq!(
+            added,
+            HashMap::from([(Fruit::Banana, 5), (Fruit::Mango, 6), (Fruit::Apple, 8)]),
+        );
+        assert_eq!(inventory.stock(&Fruit::Mango), 8);
+        assert_eq!(inventory.stock(&Fruit::Apple), 12);
+        assert_eq!(inventory.stock(&Fruit::Lychee), 5);
+        assert!(inventory.restock().is_empty());
+    }
+
+    #[test]
+    fn restock_reaches_min_stock() {
+        let mut inventory = Inventory::new(HashMap::from([(Fruit::Lychee, 1)]));
+        inventory.set_policy(
+            Fruit::Lychee,
+            RestockPolicy {
+                min_stock: 10,
+                max_capacity: 50,
+                restock_quantity: 4,
+            },
+        );
+
+        assert_eq!(inventory.restock(), HashMap::from([(Fruit::Lychee, 9)]));
+        assert_eq!(inventory.stock(&Fruit::Lychee), 10);
+    }
+
+    #[test]
+    fn consume_fruit() {
+        let mut inventory = Inventory::new(get_fruit_basket());
+
+        inventory.consume(&Fruit::Apple, 3).unwrap();
+        assert_eq!(inventory.stock(&Fruit::Apple), 1);
+        assert_eq!(
+            inventory.consume(&Fruit::Apple, 2),
+            Err(InventoryError::Shortage {
+                requested: 2,
+                available: 1,
+            }),
+        );
+        assert_eq!(
+            inventory.consume(&Fruit::Pineapple, 1),
+            Err(InventoryError::Shortage {
+                requested: 1,
+                available: 0,
+            }),
+        );
+        assert_eq!(inventory.stock(&Fruit::Apple), 1);
+
+        inventory.consume(&Fruit::Apple, 1).unwrap();
+        assert_eq!(inventory.restock(), HashMap::from([(Fruit::Apple, 5)]));
+    }
+
+    #[test]
+    fn parse_fruit_names() {
+        assert_eq!("apple".parse(), Ok(Fruit::Apple));
+        assert_eq!(" Bananas ".parse(), Ok(Fruit::Banana));
+        assert_eq!("MANGOES".parse(), Ok(Fruit::Mango));
+        assert_eq!("mangos".parse(), Ok(Fruit::Mango));
+        assert_eq!("Lychees".parse(), Ok(Fruit::Lychee));
+        assert_eq!("pineapples".parse(), Ok(Fruit::Pineapple));
+
+        for fruit in Fruit::ALL {
+            assert_eq!(fruit.to_string().parse(), Ok(fruit));
+        }
+    }
+
+    #[test]
+    fn suggest_closest_fruit() {
+        assert_eq!(
+            "bannana".parse::<Fruit>(),
+            Err(ParseFruitError {
+                input: String::from("bannana"),
+                suggestion: Some(Fruit::Banana),
+            }),
+        );
+        assert_eq!(
+            "Lichi".parse::<Fruit>().unwrap_err().to_string(),
+            "unknown fruit \"Lichi\", did you mean \"lychee\"?",
+        );
+        assert_eq!(
+            "kiwi".parse::<Fruit>().unwrap_err().to_string(),
+            "unknown fruit \"kiwi\"",
+        );
+    }
+
+    #[test]
+    fn fruits_are_ordered() {
+        let mut fruits = vec![Fruit::Pineapple, Fruit::Apple, Fruit::Lychee];
+        fruits.sort();
+
+        assert_eq!(fruits, [Fruit::Apple, Fruit::Lychee, Fruit::Pineapple]);
+        assert!(Fruit::ALL.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    fn basket(content: &[(Fruit, u32)]) -> Basket {
+        Basket::from(HashMap::from_iter(content.iter().copied()))
+    }
+
+    #[test]
+    fn merge_baskets() {
+        let store_a = basket(&[(Fruit::Apple, 4), (Fruit::Mango, 2)]);
+        let store_b = basket(&[(Fruit::Apple, 1), (Fruit::Banana, 3)]);
+
+        assert_eq!(
+            store_a.merge(&store_b),
+            Ok(basket(&[
+                (Fruit::Apple, 5),
+                (Fruit::Banana, 3),
+                (Fruit::Mango, 2)
+            ])),
+        );
+        assert_eq!(
+            store_a.merge(&basket(&[(Fruit::Mango, u32::MAX)])),
+            Err(BasketError::Overflow(Fruit::Mango)),
+        );
+    }
+
+    #[test]
+    fn difference_reports_shortfall() {
+        let stock = Basket::from(get_fruit_basket());
+        let order = basket(&[(Fruit::Apple, 4), (Fruit::Mango, 3), (Fruit::Banana, 1)]);
+
+        assert_eq!(
+            stock.difference(&order),
+            Difference {
+                remaining: basket(&[(Fruit::Lychee, 5)]),
+                shortfall: basket(&[(Fruit::Mango, 1), (Fruit::Banana, 1)]),
+            },
+        );
+        assert_eq!(
+            stock.checked_sub(&order),
+            Err(BasketError::Shortfall(basket(&[
+                (Fruit::Mango, 1),
+                (Fruit::Banana, 1),
+            ]))),
+        );
+        assert_eq!(
+            stock.checked_sub(&basket(&[(Fruit::Apple, 1), (Fruit::Lychee, 5)])),
+            Ok(basket(&[(Fruit::Apple, 3), (Fruit::Mango, 2)])),
+        );
+    }
+
+    #[test]
+    fn intersect_and_scale_baskets() {
+        let stock = Basket::from(get_fruit_basket());
+
+        assert_eq!(
+            stock.intersection(&basket(&[
+                (Fruit::Apple, 10),
+                (Fruit::Lychee, 1),
+                (Fruit::Banana, 2)
+            ])),
+            basket(&[(Fruit::Apple, 4), (Fruit::Lychee, 1)]),
+        );
+        assert_eq!(
+            stock.scaled(3),
+            Ok(basket(&[
+                (Fruit::Apple, 12),
+                (Fruit::Mango, 6),
+                (Fruit::Lychee, 15)
+            ])),
+        );
+        assert_eq!(stock.scaled(0), Ok(Basket::default()));
+        assert!(matches!(
+            stock.scaled(u32::MAX),
+            Err(BasketError::Overflow(_)),
+        ));
+    }
+
+    #[test]
+    fn display_basket_sorted_by_fruit() {
+        let mut map = get_fruit_basket();
+        fruit_basket(&mut map);
+
+        assert_eq!(
+            Basket::from(map).to_string(),
+            "apple: 4, banana: 5, mango: 2, lychee: 5, pineapple: 5",
+        );
+        assert_eq!(Basket::default().to_string(), "nothing");
+        assert_eq!(
+            BasketError::Shortfall(basket(&[(Fruit::Banana, 1)])).to_string(),
+            "missing banana: 1",
+        );
+    }
 }
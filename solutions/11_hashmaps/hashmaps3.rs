@@ -6,44 +6,824 @@
 // number of goals the team scored, and the total number of goals the team
 // conceded.
 
-use std::collections::HashMap;
+use std::borrow::Borrow;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::io::{self, BufRead};
 
 // A structure to store the goal details of a team.
-#[derive(Default)]
+#[derive(Clone, Copy, Default)]
 struct TeamScores {
-    goals_scored: u8,
-    goals_conceded: u8,
+    goals_scored: u32,
+    goals_conceded: u32,
 }
 
-fn build_scores_table(results: &str) -> HashMap<&str, TeamScores> {
+impl TeamScores {
+    // `None` if either counter would overflow.
+    fn with_match(self, scored: u8, conceded: u8) -> Option<Self> {
+        Some(Self {
+            goals_scored: self.goals_scored.checked_add(u32::from(scored))?,
+            goals_conceded: self.goals_conceded.checked_add(u32::from(conceded))?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Field {
+    Team1Name,
+    Team2Name,
+    Team1Goals,
+    Team2Goals,
+}
+
+impl Field {
+    const ALL: [Field; 4] = [
+        Field::Team1Name,
+        Field::Team2Name,
+        Field::Team1Goals,
+        Field::Team2Goals,
+    ];
+}
+
+#[derive(PartialEq, Debug)]
+enum ScoresErrorKind {
+    MissingField(Field),
+    ExtraField,
+    InvalidScore(String),
+    SelfMatch(String),
+    Overflow(String),
+}
+
+// `line` and `column` start at 1 and point at the offending field, or right
+// after the end of the line if a field is missing.
+#[derive(PartialEq, Debug)]
+struct ScoresError {
+    line: usize,
+    column: usize,
+    kind: ScoresErrorKind,
+}
+
+impl fmt::Display for ScoresError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match &self.kind {
+            ScoresErrorKind::MissingField(field) => write!(f, "missing {field:?}"),
+            ScoresErrorKind::ExtraField => f.write_str("unexpected extra field"),
+            ScoresErrorKind::InvalidScore(score) => write!(f, "{score:?} isn't a valid score"),
+            ScoresErrorKind::SelfMatch(team) => write!(f, "{team} can't play against itself"),
+            ScoresErrorKind::Overflow(team) => write!(f, "the totals of {team} overflow"),
+        }
+    }
+}
+
+impl Error for ScoresError {}
+
+struct MatchResult<'a> {
+    line: usize,
+    team_1_name: &'a str,
+    team_2_name: &'a str,
+    team_1_score: u8,
+    team_2_score: u8,
+}
+
+fn parse_line(line_number: usize, line: &str) -> Result<MatchResult<'_>, ScoresError> {
+    let error = |column, kind| ScoresError {
+        line: line_number,
+        column,
+        kind,
+    };
+
+    // Every field with the column it starts at.
+    let mut fields = Vec::new();
+    let mut column = 1;
+    for field in line.split(',') {
+        fields.push((column, field));
+        column += field.chars().count() + 1;
+    }
+    let end = column - 1;
+
+    if let Some(&(column, _)) = fields.get(Field::ALL.len()) {
+        return Err(error(column, ScoresErrorKind::ExtraField));
+    }
+    for (index, field) in Field::ALL.into_iter().enumerate() {
+        match fields.get(index) {
+            None => return Err(error(end, ScoresErrorKind::MissingField(field))),
+            Some((column, "")) => return Err(error(*column, ScoresErrorKind::MissingField(field))),
+            Some(_) => (),
+        }
+    }
+
+    let score = |(column, field): (usize, &str)| {
+        field
+            .parse()
+            .map_err(|_| error(column, ScoresErrorKind::InvalidScore(field.to_string())))
+    };
+    let result = MatchResult {
+        line: line_number,
+        team_1_name: fields[0].1,
+        team_2_name: fields[1].1,
+        team_1_score: score(fields[2])?,
+        team_2_score: score(fields[3])?,
+    };
+
+    if result.team_1_name == result.team_2_name {
+        return Err(error(
+            fields[1].0,
+            ScoresErrorKind::SelfMatch(result.team_2_name.to_string()),
+        ));
+    }
+
+    Ok(result)
+}
+
+// Works for both borrowed and owned team names. `to_key` is only called for
+// teams that aren't in the table yet, so owned names are allocated once per
+// team rather than once per line. The table is left untouched on overflow.
+fn add_result<'a, K>(
+    scores: &mut HashMap<K, TeamScores>,
+    result: MatchResult<'a>,
+    to_key: impl Fn(&'a str) -> K,
+) -> Result<(), ScoresError>
+where
+    K: Borrow<str> + Hash + Eq,
+{
+    let updated = |team: &str, scored, conceded| {
+        // Start from zeros if a team doesn't exist yet.
+        let current = scores.get(team).copied().unwrap_or_default();
+        current
+            .with_match(scored, conceded)
+            .ok_or_else(|| ScoresError {
+                line: result.line,
+                column: 1,
+                kind: ScoresErrorKind::Overflow(team.to_string()),
+            })
+    };
+    let team_1 = updated(result.team_1_name, result.team_1_score, result.team_2_score)?;
+    let team_2 = updated(result.team_2_name, result.team_2_score, result.team_1_score)?;
+
+    for (name, team) in [(result.team_1_name, team_1), (result.team_2_name, team_2)] {
+        match scores.get_mut(name) {
+            Some(current) => *current = team,
+            None => {
+                scores.insert(to_key(name), team);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Blank lines are skipped.
+fn results(results: &str) -> impl Iterator<Item = Result<MatchResult<'_>, ScoresError>> {
+    results
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| parse_line(index + 1, line))
+}
+
+// Fails at the first malformed line.
+fn try_build_scores_table(results_text: &str) -> Result<HashMap<&str, TeamScores>, ScoresError> {
     // The name of the team is the key and its associated struct is the value.
     let mut scores = HashMap::new();
 
-    for line in results.lines() {
-        let mut split_iterator = line.split(',');
-        // NOTE: We use `unwrap` because we didn't deal with error handling yet.
-        let team_1_name = split_iterator.next().unwrap();
-        let team_2_name = split_iterator.next().unwrap();
-        let team_1_score: u8 = split_iterator.next().unwrap().parse().unwrap();
-        let team_2_score: u8 = split_iterator.next().unwrap().parse().unwrap();
+    for result in results(results_text) {
+        add_result(&mut scores, result?, |name| name)?;
+    }
+
+    Ok(scores)
+}
+
+// Builds the table from the well-formed lines and returns the errors of all
+// the others.
+fn build_scores_table_lenient(results_text: &str) -> (HashMap<&str, TeamScores>, Vec<ScoresError>) {
+    let mut scores = HashMap::new();
+    let mut errors = Vec::new();
+
+    for result in results(results_text) {
+        match result {
+            Ok(result) => {
+                if let Err(e) = add_result(&mut scores, result, |name| name) {
+                    errors.push(e);
+                }
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+
+    (scores, errors)
+}
+
+fn build_scores_table(results: &str) -> HashMap<&str, TeamScores> {
+    // The message of the panic names the line and column of the first
+    // malformed result.
+    match try_build_scores_table(results) {
+        Ok(scores) => scores,
+        Err(e) => panic!("{e}"),
+    }
+}
+
+#[derive(Debug)]
+enum ReadScoresError {
+    Io(io::Error),
+    Scores(ScoresError),
+}
+
+impl fmt::Display for ReadScoresError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read the results: {e}"),
+            Self::Scores(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for ReadScoresError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Scores(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for ReadScoresError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<ScoresError> for ReadScoresError {
+    fn from(e: ScoresError) -> Self {
+        Self::Scores(e)
+    }
+}
+
+// Builds a table with owned team names one line at a time, so the input never
+// has to be held in memory as a whole.
+#[derive(Default)]
+struct ScoresTableBuilder {
+    scores: HashMap<String, TeamScores>,
+    lines: usize,
+}
+
+impl ScoresTableBuilder {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    // Blank lines are skipped but still counted for error positions.
+    fn add_line(&mut self, line: &str) -> Result<(), ScoresError> {
+        self.lines += 1;
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+        let result = parse_line(self.lines, line)?;
+        add_result(&mut self.scores, result, str::to_string)
+    }
+
+    // Reuses a single buffer for every line. Stops at the first I/O error or
+    // malformed line.
+    fn read(mut self, mut reader: impl BufRead) -> Result<Self, ReadScoresError> {
+        let mut buffer = String::new();
+        loop {
+            buffer.clear();
+            if reader.read_line(&mut buffer)? == 0 {
+                return Ok(self);
+            }
+            let line = buffer.strip_suffix('\n').unwrap_or(&buffer);
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            self.add_line(line)?;
+        }
+    }
+
+    fn build(self) -> HashMap<String, TeamScores> {
+        self.scores
+    }
+}
+
+fn read_scores_table(reader: impl BufRead) -> Result<HashMap<String, TeamScores>, ReadScoresError> {
+    Ok(ScoresTableBuilder::new().read(reader)?.build())
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct PointsRules {
+    win: u32,
+    draw: u32,
+    loss: u32,
+}
+
+impl Default for PointsRules {
+    fn default() -> Self {
+        Self {
+            win: 3,
+            draw: 1,
+            loss: 0,
+        }
+    }
+}
+
+impl PointsRules {
+    fn points(&self, scored: u8, conceded: u8) -> u32 {
+        match scored.cmp(&conceded) {
+            Ordering::Greater => self.win,
+            Ordering::Equal => self.draw,
+            Ordering::Less => self.loss,
+        }
+    }
+}
+
+// Decides the order of teams with the same number of points, in the order they
+// are given. `HeadToHead` only counts the points from matches between the
+// teams that are still tied.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum TieBreaker {
+    GoalDifference,
+    GoalsScored,
+    HeadToHead,
+    Name,
+}
+
+#[derive(Clone, PartialEq, Default, Debug)]
+struct Standing {
+    team: String,
+    played: u32,
+    won: u32,
+    drawn: u32,
+    lost: u32,
+    goals_for: u32,
+    goals_against: u32,
+    points: u32,
+}
+
+impl Standing {
+    fn goal_difference(&self) -> i64 {
+        i64::from(self.goals_for) - i64::from(self.goals_against)
+    }
+
+    // `None` if any counter would overflow.
+    fn with_match(&self, scored: u8, conceded: u8, rules: &PointsRules) -> Option<Self> {
+        let mut standing = self.clone();
+        standing.played = standing.played.checked_add(1)?;
+        let outcome = match scored.cmp(&conceded) {
+            Ordering::Greater => &mut standing.won,
+            Ordering::Equal => &mut standing.drawn,
+            Ordering::Less => &mut standing.lost,
+        };
+        *outcome = outcome.checked_add(1)?;
+        standing.goals_for = standing.goals_for.checked_add(u32::from(scored))?;
+        standing.goals_against = standing.goals_against.checked_add(u32::from(conceded))?;
+        standing.points = standing
+            .points
+            .checked_add(rules.points(scored, conceded))?;
+        Some(standing)
+    }
+}
+
+#[derive(Debug)]
+struct League {
+    rules: PointsRules,
+    tie_breakers: Vec<TieBreaker>,
+    standings: HashMap<String, Standing>,
+    // (team 1, team 2, team 1 goals, team 2 goals)
+    matches: Vec<(String, String, u8, u8)>,
+}
+
+impl League {
+    fn new(rules: PointsRules, tie_breakers: Vec<TieBreaker>) -> Self {
+        Self {
+            rules,
+            tie_breakers,
+            standings: HashMap::new(),
+            matches: Vec::new(),
+        }
+    }
+
+    fn from_results(
+        results_text: &str,
+        rules: PointsRules,
+        tie_breakers: Vec<TieBreaker>,
+    ) -> Result<Self, ScoresError> {
+        let mut league = Self::new(rules, tie_breakers);
+        for result in results(results_text) {
+            league.add(&result?)?;
+        }
+        Ok(league)
+    }
 
-        // Insert the default with zeros if a team doesn't exist yet.
-        let team_1 = scores
-            .entry(team_1_name)
-            .or_insert_with(TeamScores::default);
-        // Update the values.
-        team_1.goals_scored += team_1_score;
-        team_1.goals_conceded += team_2_score;
+    // The league is left untouched if a counter of either team overflows.
+    fn add(&mut self, result: &MatchResult) -> Result<(), ScoresError> {
+        let updated = |team: &str, scored, conceded| {
+            let standing = match self.standings.get(team) {
+                Some(standing) => standing.with_match(scored, conceded, &self.rules),
+                None => Standing {
+                    team: team.to_string(),
+                    ..Standing::default()
+                }
+                .with_match(scored, conceded, &self.rules),
+            };
+            standing.ok_or_else(|| ScoresError {
+                line: result.line,
+                column: 1,
+                kind: ScoresErrorKind::Overflow(team.to_string()),
+            })
+        };
+        let team_1 = updated(result.team_1_name, result.team_1_score, result.team_2_score)?;
+        let team_2 = updated(result.team_2_name, result.team_2_score, result.team_1_score)?;
 
-        // Similarly for the second team.
-        let team_2 = scores
-            .entry(team_2_name)
-            .or_insert_with(TeamScores::default);
-        team_2.goals_scored += team_2_score;
-        team_2.goals_conceded += team_1_score;
+        for standing in [team_1, team_2] {
+            self.standings.insert(standing.team.clone(), standing);
+        }
+
+        self.matches.push((
+            result.team_1_name.to_string(),
+            result.team_2_name.to_string(),
+            result.team_1_score,
+            result.team_2_score,
+        ));
+        Ok(())
+    }
+
+    // The points each team in `group` earned against the others in it. They're
+    // part of the team's total points, which `add` checked for overflow, so
+    // the sums saturate only in theory.
+    fn head_to_head_points(&self, group: &[&Standing]) -> HashMap<String, u32> {
+        let in_group = |team: &str| group.iter().any(|standing| standing.team == team);
+        let mut points: HashMap<String, u32> = HashMap::new();
+
+        for (team_1, team_2, score_1, score_2) in &self.matches {
+            if in_group(team_1) && in_group(team_2) {
+                let points_1 = points.entry(team_1.clone()).or_default();
+                *points_1 = points_1.saturating_add(self.rules.points(*score_1, *score_2));
+                let points_2 = points.entry(team_2.clone()).or_default();
+                *points_2 = points_2.saturating_add(self.rules.points(*score_2, *score_1));
+            }
+        }
+
+        points
     }
 
-    scores
+    // Sorts `group` by `tie_breaker` and splits it into the groups of teams
+    // that are still tied.
+    fn break_ties<'a>(
+        &self,
+        mut group: Vec<&'a Standing>,
+        tie_breaker: TieBreaker,
+    ) -> Vec<Vec<&'a Standing>> {
+        if tie_breaker == TieBreaker::Name {
+            group.sort_by(|a, b| a.team.cmp(&b.team));
+            return group.into_iter().map(|standing| vec![standing]).collect();
+        }
+
+        let head_to_head = match tie_breaker {
+            TieBreaker::HeadToHead => self.head_to_head_points(&group),
+            _ => HashMap::new(),
+        };
+        let key = |standing: &Standing| match tie_breaker {
+            TieBreaker::GoalDifference => standing.goal_difference(),
+            TieBreaker::GoalsScored => i64::from(standing.goals_for),
+            TieBreaker::HeadToHead => {
+                i64::from(head_to_head.get(&standing.team).copied().unwrap_or(0))
+            }
+            TieBreaker::Name => unreachable!(),
+        };
+        group.sort_by_key(|standing| Reverse(key(standing)));
+
+        let mut groups: Vec<Vec<&Standing>> = Vec::new();
+        for standing in group {
+            match groups.last_mut() {
+                Some(last) if key(last[0]) == key(standing) => last.push(standing),
+                _ => groups.push(vec![standing]),
+            }
+        }
+        groups
+    }
+
+    // Sorted by points, then by the tie breakers. Teams that are tied on
+    // everything are sorted by name.
+    fn standings(&self) -> Vec<Standing> {
+        let mut teams: Vec<&Standing> = self.standings.values().collect();
+        teams.sort_by(|a, b| b.points.cmp(&a.points).then_with(|| a.team.cmp(&b.team)));
+
+        let mut groups: Vec<Vec<&Standing>> = Vec::new();
+        for standing in teams {
+            match groups.last_mut() {
+                Some(last) if last[0].points == standing.points => last.push(standing),
+                _ => groups.push(vec![standing]),
+            }
+        }
+
+        for &tie_breaker in &self.tie_breakers {
+            groups = groups
+                .into_iter()
+                .flat_map(|group| {
+                    if group.len() > 1 {
+                        self.break_ties(group, tie_breaker)
+                    } else {
+                        vec![group]
+                    }
+                })
+                .collect();
+        }
+
+        groups.into_iter().flatten().cloned().collect()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum StandingsFormat {
+    // Aligned columns for terminals and plain-text reports.
+    Table,
+    // RFC 4180: CRLF line endings and quoted fields where needed.
+    Csv,
+    // An array with one object per team. Only ASCII is emitted.
+    Json,
+}
+
+const STANDINGS_HEADER: [&str; 10] = ["Pos", "Team", "P", "W", "D", "L", "GF", "GA", "GD", "Pts"];
+
+// The position is the index in `standings` plus one, so pass them in the order
+// returned by `League::standings`.
+fn render_standings(standings: &[Standing], format: StandingsFormat) -> String {
+    match format {
+        StandingsFormat::Table => render_table(standings),
+        StandingsFormat::Csv => render_csv(standings),
+        StandingsFormat::Json => render_json(standings),
+    }
+}
+
+fn standing_row(position: usize, standing: &Standing) -> [String; 10] {
+    [
+        position.to_string(),
+        standing.team.clone(),
+        standing.played.to_string(),
+        standing.won.to_string(),
+        standing.drawn.to_string(),
+        standing.lost.to_string(),
+        standing.goals_for.to_string(),
+        standing.goals_against.to_string(),
+        standing.goal_difference().to_string(),
+        standing.points.to_string(),
+    ]
+}
+
+// The team is left aligned and every number right aligned. Widths are counted
+// in characters, so double-width characters still misalign the columns.
+fn render_table(standings: &[Standing]) -> String {
+    let rows: Vec<[String; 10]> = standings
+        .iter()
+        .enumerate()
+        .map(|(index, standing)| {
+            let mut row = standing_row(index + 1, standing);
+            row[1] = display_name(&standing.team);
+            row
+        })
+        .collect();
+
+    let mut widths = STANDINGS_HEADER.map(|title| title.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut output = String::new();
+    let header = STANDINGS_HEADER.map(String::from);
+    for row in std::iter::once(&header).chain(&rows) {
+        let mut line = String::new();
+        for (column, (cell, width)) in row.iter().zip(widths).enumerate() {
+            if column > 0 {
+                line.push_str("  ");
+            }
+            if column == 1 {
+                line.push_str(&format!("{cell:<width$}"));
+            } else {
+                line.push_str(&format!("{cell:>width$}"));
+            }
+        }
+        output.push_str(line.trim_end());
+        output.push('\n');
+    }
+
+    output
+}
+
+// A tab or line break in a team name would shift every column after it, so
+// control characters are shown as escapes like `\t` in the table.
+fn display_name(team: &str) -> String {
+    team.chars()
+        .map(|c| {
+            if c.is_control() {
+                c.escape_default().to_string()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn render_csv(standings: &[Standing]) -> String {
+    let mut output = String::from(
+        "position,team,played,won,drawn,lost,goals_for,goals_against,goal_difference,points\r\n",
+    );
+    for (index, standing) in standings.iter().enumerate() {
+        let row = standing_row(index + 1, standing);
+        let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        output.push_str(&fields.join(","));
+        output.push_str("\r\n");
+    }
+    output
+}
+
+// Non-ASCII characters are written as `\u` escapes (with surrogate pairs
+// outside the Basic Multilingual Plane) so the output survives any transport.
+fn json_string(value: &str) -> String {
+    let mut output = String::from('"');
+    for c in value.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            ' '..='~' => output.push(c),
+            _ => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    output.push_str(&format!("\\u{unit:04x}"));
+                }
+            }
+        }
+    }
+    output.push('"');
+    output
+}
+
+fn render_json(standings: &[Standing]) -> String {
+    if standings.is_empty() {
+        return String::from("[]\n");
+    }
+
+    let objects: Vec<String> = standings
+        .iter()
+        .enumerate()
+        .map(|(index, s)| {
+            format!(
+                "  {{\"position\": {}, \"team\": {}, \"played\": {}, \"won\": {}, \"drawn\": {}, \
+                 \"lost\": {}, \"goals_for\": {}, \"goals_against\": {}, \
+                 \"goal_difference\": {}, \"points\": {}}}",
+                index + 1,
+                json_string(&s.team),
+                s.played,
+                s.won,
+                s.drawn,
+                s.lost,
+                s.goals_for,
+                s.goals_against,
+                s.goal_difference(),
+                s.points,
+            )
+        })
+        .collect();
+
+    format!("[\n{}\n]\n", objects.join(",\n"))
+}
+
+#[derive(PartialEq, Debug)]
+enum FixtureError {
+    DuplicateTeam(String),
+    // Empty names and names with commas or line breaks can't be written as a
+    // `Home,Away` line.
+    InvalidTeamName(String),
+}
+
+impl fmt::Display for FixtureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DuplicateTeam(team) => write!(f, "{team} is listed more than once"),
+            Self::InvalidTeamName(team) => write!(f, "{team:?} isn't a valid team name"),
+        }
+    }
+}
+
+impl Error for FixtureError {}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum RoundRobin {
+    // Every team plays every other team once.
+    Single,
+    // A second leg repeats the first one with home and away swapped.
+    Double,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Fixture<'a> {
+    home: &'a str,
+    away: &'a str,
+}
+
+impl fmt::Display for Fixture<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{}", self.home, self.away)
+    }
+}
+
+// The teams of a scores table, sorted by name so the schedule doesn't depend on
+// the iteration order of the map.
+fn teams<K: Borrow<str>>(scores: &HashMap<K, TeamScores>) -> Vec<&str> {
+    let mut teams: Vec<&str> = scores.keys().map(Borrow::borrow).collect();
+    teams.sort_unstable();
+    teams
+}
+
+// Schedules the rounds with the circle method: the first slot stays put while
+// the others rotate around it. With an odd number of teams, the fixed slot is
+// left empty and the team paired with it has a bye that round. Home and away
+// follow Berger tables, so no team plays more than two matches in a row at the
+// same venue (three across the two legs of a double round robin). Over a
+// single round robin every team has at most one home match more than away
+// matches, and exactly as many of each over a double one.
+fn round_robin<'a>(
+    teams: &[&'a str],
+    kind: RoundRobin,
+) -> Result<Vec<Vec<Fixture<'a>>>, FixtureError> {
+    let mut seen = HashSet::new();
+    for &team in teams {
+        if team.is_empty() || team.contains([',', '\r', '\n']) {
+            return Err(FixtureError::InvalidTeamName(team.to_string()));
+        }
+        if !seen.insert(team) {
+            return Err(FixtureError::DuplicateTeam(team.to_string()));
+        }
+    }
+
+    let mut slots: Vec<Option<&str>> = teams.iter().copied().map(Some).collect();
+    if slots.len() % 2 == 1 {
+        slots.insert(0, None);
+    }
+    let n = slots.len();
+
+    let mut rounds = Vec::new();
+    for round in 0..n.saturating_sub(1) {
+        let mut fixtures = Vec::new();
+        for i in 0..n / 2 {
+            let (mut home, mut away) = (slots[i], slots[n - 1 - i]);
+            // Alternating by slot makes the rotating teams alternate venues,
+            // and the fixed team would otherwise always play at home.
+            if (i == 0 && round % 2 == 1) || i % 2 == 1 {
+                (home, away) = (away, home);
+            }
+            if let (Some(home), Some(away)) = (home, away) {
+                fixtures.push(Fixture { home, away });
+            }
+        }
+        rounds.push(fixtures);
+        slots[1..].rotate_right(1);
+    }
+
+    if kind == RoundRobin::Double {
+        let second_leg: Vec<Vec<Fixture>> = rounds
+            .iter()
+            .map(|round| {
+                round
+                    .iter()
+                    .map(|fixture| Fixture {
+                        home: fixture.away,
+                        away: fixture.home,
+                    })
+                    .collect()
+            })
+            .collect();
+        rounds.extend(second_leg);
+    }
+
+    Ok(rounds)
+}
+
+// One `Home,Away` line per match with a blank line between rounds. Appending
+// `,<home goals>,<away goals>` to every line gives input for
+// `build_scores_table`, which skips the blank lines.
+fn fixtures_text(rounds: &[Vec<Fixture>]) -> String {
+    rounds
+        .iter()
+        .map(|round| {
+            round
+                .iter()
+                .map(|fixture| format!("{fixture}\n"))
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 fn main() {
@@ -84,4 +864,428 @@ England,Spain,1,0";
         assert_eq!(team.goals_scored, 0);
         assert_eq!(team.goals_conceded, 3);
     }
+
+    fn error(results: &str) -> (usize, usize, ScoresErrorKind) {
+        let Err(e) = try_build_scores_table(results) else {
+            panic!("expected an error for {results:?}");
+        };
+        (e.line, e.column, e.kind)
+    }
+
+    #[test]
+    fn fail_with_line_and_column() {
+        assert_eq!(
+            error("England,France,4,2\nFrance,Italy,3"),
+            (2, 15, ScoresErrorKind::MissingField(Field::Team2Goals)),
+        );
+        assert_eq!(
+            error("England,,4,2"),
+            (1, 9, ScoresErrorKind::MissingField(Field::Team2Name)),
+        );
+        assert_eq!(
+            error("England,France,4,2,extra"),
+            (1, 20, ScoresErrorKind::ExtraField),
+        );
+        assert_eq!(
+            error("England,France,4,2\n\nFrance,Italy,three,1"),
+            (3, 14, ScoresErrorKind::InvalidScore(String::from("three"))),
+        );
+        assert_eq!(
+            error("Côte d'Ivoire,Côte d'Ivoire,1,1"),
+            (
+                1,
+                15,
+                ScoresErrorKind::SelfMatch(String::from("Côte d'Ivoire"))
+            ),
+        );
+        assert_eq!(
+            try_build_scores_table("England,France,4,-2")
+                .err()
+                .unwrap()
+                .to_string(),
+            "line 1, column 18: \"-2\" isn't a valid score",
+        );
+    }
+
+    #[test]
+    fn lenient_mode_keeps_good_lines() {
+        let results = "England,France,4,2
+France,Italy,x,1
+Poland,Spain,2,0
+Spain,Spain,1,1
+Germany,England,2";
+
+        let (scores, errors) = build_scores_table_lenient(results);
+
+        assert_eq!(scores.len(), 4);
+        assert_eq!(scores.get("England").unwrap().goals_scored, 4);
+        assert_eq!(scores.get("Spain").unwrap().goals_conceded, 2);
+        assert!(!scores.contains_key("Italy"));
+        assert!(!scores.contains_key("Germany"));
+        assert_eq!(errors.iter().map(|e| e.line).collect::<Vec<_>>(), [2, 4, 5],);
+    }
+
+    #[test]
+    fn count_goals_past_u8() {
+        let results = "England,France,200,0\nEngland,Spain,200,255\n";
+        let scores = build_scores_table(results);
+        assert_eq!(scores.get("England").unwrap().goals_scored, 400);
+        assert_eq!(scores.get("England").unwrap().goals_conceded, 255);
+
+        let mut scores = HashMap::from([(
+            "England",
+            TeamScores {
+                goals_scored: u32::MAX - 1,
+                goals_conceded: 0,
+            },
+        )]);
+        let result = parse_line(7, "France,England,1,2").unwrap();
+        let e = add_result(&mut scores, result, |name| name).unwrap_err();
+        assert_eq!(e.line, 7);
+        assert_eq!(e.kind, ScoresErrorKind::Overflow(String::from("England")));
+        // Neither team is updated when one of them overflows.
+        assert!(!scores.contains_key("France"));
+        assert_eq!(scores.get("England").unwrap().goals_scored, u32::MAX - 1);
+    }
+
+    #[test]
+    fn read_scores_from_reader() {
+        let input = RESULTS.replace('\n', "\r\n") + "\r\n\r\n";
+        let scores = read_scores_table(input.as_bytes()).unwrap();
+
+        assert_eq!(scores.len(), 6);
+        let england = scores.get("England").unwrap();
+        assert_eq!((england.goals_scored, england.goals_conceded), (6, 4));
+        let spain = scores.get("Spain").unwrap();
+        assert_eq!((spain.goals_scored, spain.goals_conceded), (0, 3));
+
+        let Err(ReadScoresError::Scores(e)) =
+            read_scores_table("England,France,4,2\n\nFrance,Italy,3\n".as_bytes())
+        else {
+            panic!("expected a scores error");
+        };
+        assert_eq!((e.line, e.column), (3, 15));
+
+        let Err(ReadScoresError::Io(e)) = read_scores_table(&b"England,France,4,2\n\xff"[..])
+        else {
+            panic!("expected an I/O error");
+        };
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
+    fn table(standings: &[Standing]) -> Vec<(&str, u32, u32, u32, u32, i64, u32)> {
+        standings
+            .iter()
+            .map(|s| {
+                (
+                    s.team.as_str(),
+                    s.played,
+                    s.won,
+                    s.drawn,
+                    s.lost,
+                    s.goal_difference(),
+                    s.points,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn league_standings() {
+        let league = League::from_results(
+            RESULTS,
+            PointsRules::default(),
+            vec![TieBreaker::GoalDifference, TieBreaker::GoalsScored],
+        )
+        .unwrap();
+
+        assert_eq!(
+            table(&league.standings()),
+            [
+                ("England", 3, 2, 0, 1, 2, 6),
+                ("Poland", 1, 1, 0, 0, 2, 3),
+                ("Germany", 1, 1, 0, 0, 1, 3),
+                ("France", 2, 1, 0, 1, 0, 3),
+                ("Italy", 1, 0, 0, 1, -2, 0),
+                ("Spain", 2, 0, 0, 2, -3, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn configurable_points_rules() {
+        let rules = PointsRules {
+            win: 2,
+            draw: 1,
+            loss: 0,
+        };
+        let league =
+            League::from_results("Italy,Spain,1,1\nItaly,France,2,0", rules, vec![]).unwrap();
+
+        assert_eq!(
+            table(&league.standings()),
+            [
+                ("Italy", 2, 1, 1, 0, 2, 3),
+                ("Spain", 1, 0, 1, 0, 0, 1),
+                ("France", 1, 0, 0, 1, -2, 0),
+            ],
+        );
+    }
+
+    // Every team has 3 points and a goal difference of 0.
+    const TIED: &str = "Zebras,Ants,2,1
+Zebras,Moles,0,1
+Ants,Moles,1,0";
+
+    fn order(tie_breakers: Vec<TieBreaker>) -> Vec<String> {
+        League::from_results(TIED, PointsRules::default(), tie_breakers)
+            .unwrap()
+            .standings()
+            .into_iter()
+            .map(|standing| standing.team)
+            .collect()
+    }
+
+    #[test]
+    fn configurable_tie_breakers() {
+        assert_eq!(
+            order(vec![TieBreaker::GoalsScored, TieBreaker::HeadToHead]),
+            ["Zebras", "Ants", "Moles"],
+        );
+        assert_eq!(
+            order(vec![TieBreaker::GoalsScored, TieBreaker::Name]),
+            ["Ants", "Zebras", "Moles"],
+        );
+        // All three teams won one of their matches against each other.
+        assert_eq!(
+            order(vec![TieBreaker::HeadToHead]),
+            ["Ants", "Moles", "Zebras"],
+        );
+        assert_eq!(
+            order(vec![TieBreaker::GoalDifference, TieBreaker::GoalsScored]),
+            ["Ants", "Zebras", "Moles"],
+        );
+    }
+
+    #[test]
+    fn fail_on_standing_overflow() {
+        let rules = PointsRules {
+            win: u32::MAX / 2 + 1,
+            ..PointsRules::default()
+        };
+        let e = League::from_results("England,France,1,0\nEngland,Spain,2,0", rules, vec![])
+            .err()
+            .unwrap();
+        assert_eq!((e.line, e.column), (2, 1));
+        assert_eq!(e.kind, ScoresErrorKind::Overflow(String::from("England")));
+
+        let mut league = League::new(rules, vec![]);
+        league
+            .add(&parse_line(1, "England,France,1,0").unwrap())
+            .unwrap();
+        assert!(league
+            .add(&parse_line(2, "Spain,England,0,3").unwrap())
+            .is_err());
+        // Spain isn't added when England overflows.
+        assert_eq!(table(&league.standings()).len(), 2);
+        assert_eq!(league.standings()[0].goals_for, 1);
+    }
+
+    #[test]
+    fn fail_building_league_from_bad_results() {
+        assert_eq!(
+            League::from_results("England,France,4", PointsRules::default(), vec![])
+                .err()
+                .map(|e| e.kind),
+            Some(ScoresErrorKind::MissingField(Field::Team2Goals)),
+        );
+    }
+
+    // Names that need escaping can't come from the comma separated input, so
+    // the matches are added directly.
+    fn rendered_standings() -> Vec<Standing> {
+        let mut league = League::new(
+            PointsRules::default(),
+            vec![TieBreaker::GoalDifference, TieBreaker::GoalsScored],
+        );
+        for (team_1_name, team_2_name, team_1_score, team_2_score) in [
+            ("Côte d'Ivoire", "Hamburg, SV", 3, 1),
+            ("Sporting \"B\"", "Côte d'Ivoire", 0, 0),
+            ("Hamburg, SV", "Sporting \"B\"", 2, 2),
+        ] {
+            league
+                .add(&MatchResult {
+                    line: 0,
+                    team_1_name,
+                    team_2_name,
+                    team_1_score,
+                    team_2_score,
+                })
+                .unwrap();
+        }
+        league.standings()
+    }
+
+    #[test]
+    fn render_standings_table() {
+        assert_eq!(
+            render_standings(&rendered_standings(), StandingsFormat::Table),
+            "\
+Pos  Team           P  W  D  L  GF  GA  GD  Pts
+  1  Côte d'Ivoire  2  1  1  0   3   1   2    4
+  2  Sporting \"B\"   2  0  2  0   2   2   0    2
+  3  Hamburg, SV    2  0  1  1   3   5  -2    1
+",
+        );
+        assert_eq!(display_name("Tab\tUnited\n"), "Tab\\tUnited\\n");
+    }
+
+    #[test]
+    fn render_standings_csv() {
+        assert_eq!(
+            render_standings(&rendered_standings(), StandingsFormat::Csv),
+            "\
+position,team,played,won,drawn,lost,goals_for,goals_against,goal_difference,points\r
+1,Côte d'Ivoire,2,1,1,0,3,1,2,4\r
+2,\"Sporting \"\"B\"\"\",2,0,2,0,2,2,0,2\r
+3,\"Hamburg, SV\",2,0,1,1,3,5,-2,1\r
+",
+        );
+    }
+
+    #[test]
+    fn render_standings_json() {
+        assert_eq!(
+            render_standings(&rendered_standings(), StandingsFormat::Json),
+            r#"[
+  {"position": 1, "team": "C\u00f4te d'Ivoire", "played": 2, "won": 1, "drawn": 1, "lost": 0, "goals_for": 3, "goals_against": 1, "goal_difference": 2, "points": 4},
+  {"position": 2, "team": "Sporting \"B\"", "played": 2, "won": 0, "drawn": 2, "lost": 0, "goals_for": 2, "goals_against": 2, "goal_difference": 0, "points": 2},
+  {"position": 3, "team": "Hamburg, SV", "played": 2, "won": 0, "drawn": 1, "lost": 1, "goals_for": 3, "goals_against": 5, "goal_difference": -2, "points": 1}
+]
+"#,
+        );
+        assert_eq!(render_standings(&[], StandingsFormat::Json), "[]\n");
+        assert_eq!(
+            json_string("Tab\there\\ 🦁\u{1}"),
+            r#""Tab\there\\ \ud83e\udd81\u0001""#,
+        );
+    }
+
+    #[test]
+    fn single_round_robin() {
+        let rounds = round_robin(&["A", "B", "C", "D"], RoundRobin::Single).unwrap();
+        assert_eq!(
+            fixtures_text(&rounds),
+            "\
+A,D
+C,B
+
+C,A
+B,D
+
+A,B
+D,C
+",
+        );
+
+        let rounds = round_robin(&["A", "B", "C"], RoundRobin::Single).unwrap();
+        assert_eq!(fixtures_text(&rounds), "B,A\n\nA,C\n\nC,B\n");
+    }
+
+    #[test]
+    fn round_robin_properties() {
+        let names: Vec<String> = (1..=21).map(|i| format!("Team {i}")).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        for n in 0..=names.len() {
+            let teams = &names[..n];
+            for kind in [RoundRobin::Single, RoundRobin::Double] {
+                let legs = if kind == RoundRobin::Single { 1 } else { 2 };
+                let rounds = round_robin(teams, kind).unwrap();
+                let rounds_per_leg = if n % 2 == 0 { n.saturating_sub(1) } else { n };
+                assert_eq!(rounds.len(), legs * rounds_per_leg);
+
+                let mut pairs = HashMap::new();
+                // Whether each match of a team is at home, in order.
+                let mut venues: HashMap<&str, Vec<bool>> = HashMap::new();
+                for round in &rounds {
+                    let mut playing = HashSet::new();
+                    for fixture in round {
+                        assert!(playing.insert(fixture.home));
+                        assert!(playing.insert(fixture.away));
+                        *pairs.entry((fixture.home, fixture.away)).or_insert(0) += 1;
+                        venues.entry(fixture.home).or_default().push(true);
+                        venues.entry(fixture.away).or_default().push(false);
+                    }
+                    // At most one team has a bye.
+                    assert_eq!(playing.len(), n - n % 2);
+                }
+
+                for &a in teams {
+                    let venues = venues.get(a).map_or(&[][..], Vec::as_slice);
+                    assert_eq!(venues.len(), legs * (n - 1));
+                    let home = venues.iter().filter(|&&home| home).count();
+                    let away = venues.len() - home;
+                    let streak = venues
+                        .chunk_by(|a, b| a == b)
+                        .map(<[bool]>::len)
+                        .max()
+                        .unwrap_or(0);
+                    match kind {
+                        RoundRobin::Single => {
+                            assert!(home.abs_diff(away) <= 1);
+                            assert!(streak <= 2, "{a} plays {streak} in a row at one venue");
+                        }
+                        RoundRobin::Double => {
+                            assert_eq!(home, away);
+                            assert!(streak <= 3, "{a} plays {streak} in a row at one venue");
+                        }
+                    }
+                    for &b in teams.iter().filter(|&&b| b != a) {
+                        let ab = pairs.get(&(a, b)).copied().unwrap_or(0);
+                        let ba = pairs.get(&(b, a)).copied().unwrap_or(0);
+                        match kind {
+                            RoundRobin::Single => assert_eq!(ab + ba, 1),
+                            RoundRobin::Double => assert_eq!((ab, ba), (1, 1)),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn simulate_season_from_scores_table() {
+        let scores = build_scores_table(RESULTS);
+        let rounds = round_robin(&teams(&scores), RoundRobin::Double).unwrap();
+        let season: String = fixtures_text(&rounds)
+            .lines()
+            .map(|line| match line {
+                "" => String::from("\n"),
+                fixture => format!("{fixture},1,0\n"),
+            })
+            .collect();
+
+        let season = build_scores_table(&season);
+        assert_eq!(season.len(), 6);
+        for team in season.values() {
+            // 5 home wins and 5 away defeats.
+            assert_eq!((team.goals_scored, team.goals_conceded), (5, 5));
+        }
+    }
+
+    #[test]
+    fn fail_scheduling_bad_teams() {
+        assert_eq!(
+            round_robin(&["England", "France", "England"], RoundRobin::Single),
+            Err(FixtureError::DuplicateTeam(String::from("England"))),
+        );
+        assert_eq!(
+            round_robin(&["England", "Hamburg, SV"], RoundRobin::Single),
+            Err(FixtureError::InvalidTeamName(String::from("Hamburg, SV"))),
+        );
+        assert_eq!(
+            round_robin(&["", "France"], RoundRobin::Double),
+            Err(FixtureError::InvalidTeamName(String::new())),
+        );
+    }
 }